@@ -0,0 +1,26 @@
+create table IF NOT EXISTS albums (
+    id uuid primary key not null,
+    name text not null,
+    ownerId INTEGER not null,
+    private BOOLEAN not null DEFAULT 0,
+    createdDate timestamp not null,
+    FOREIGN KEY(ownerId) REFERENCES users(id) ON DELETE CASCADE
+);
+
+create table IF NOT EXISTS albumFile (
+    albumID uuid not null,
+    fileID uuid not null,
+    PRIMARY KEY(albumID, fileID),
+    FOREIGN KEY(albumID) REFERENCES albums(id) ON DELETE CASCADE,
+    FOREIGN KEY(fileID) REFERENCES files(id) ON DELETE CASCADE
+);
+
+-- Members of an album besides the owner. role is either 'viewer' or 'contributor'.
+create table IF NOT EXISTS albumAccess (
+    albumID uuid not null,
+    userID INTEGER not null,
+    role text not null,
+    PRIMARY KEY(albumID, userID),
+    FOREIGN KEY(albumID) REFERENCES albums(id) ON DELETE CASCADE,
+    FOREIGN KEY(userID) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS shares (
+    token TEXT NOT NULL UNIQUE PRIMARY KEY,
+    fileID uuid NULL REFERENCES files(id) ON DELETE CASCADE,
+    albumID uuid NULL REFERENCES albums(id) ON DELETE CASCADE,
+    created_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMP NULL,
+    password TEXT NULL,
+    allow_download BOOLEAN NOT NULL DEFAULT 0,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    -- A link shares exactly one photo or one album
+    CHECK ((fileID IS NULL) != (albumID IS NULL))
+);
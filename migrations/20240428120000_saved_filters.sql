@@ -0,0 +1,10 @@
+create table IF NOT EXISTS savedFilters (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    name text not null,
+    -- NULL for filters shared with everyone
+    userID INTEGER null,
+    -- Serialized Filters struct
+    filter text not null,
+    createdDate timestamp not null,
+    FOREIGN KEY(userID) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,13 @@
+-- Cover photo of an album. Cleared when the photo is deleted.
+ALTER TABLE albums ADD COLUMN coverID uuid NULL REFERENCES files(id) ON DELETE SET NULL;
+
+-- Manual ordering of photos within an album, lowest first
+ALTER TABLE albumFile ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+
+-- Keep the existing albums in upload order
+UPDATE albumFile SET position = (
+    SELECT COUNT(*) FROM albumFile af
+    INNER JOIN files f ON f.id = af.fileID
+    WHERE af.albumID = albumFile.albumID
+    AND f.uploadDate < (SELECT uploadDate FROM files WHERE id = albumFile.fileID)
+);
@@ -0,0 +1,57 @@
+ALTER TABLE files ADD COLUMN caption text NULL;
+
+-- Full text index of captions, tags, people and locations, one row per file.
+-- Kept in sync by the triggers below.
+CREATE VIRTUAL TABLE IF NOT EXISTS filesSearch USING fts5(
+    fileID UNINDEXED,
+    caption,
+    tags,
+    people,
+    location
+);
+
+INSERT INTO filesSearch (fileID, caption, tags, people, location)
+SELECT f.id, COALESCE(f.caption, ''),
+    COALESCE((SELECT group_concat(tf.tagString, ' ') FROM tagFile tf WHERE tf.fileID = f.id), ''),
+    COALESCE((SELECT group_concat(u.username, ' ') FROM userFile uf INNER JOIN users u ON u.id = uf.userID WHERE uf.fileID = f.id), ''),
+    COALESCE(f.location, '')
+FROM files f;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchInsert AFTER INSERT ON files BEGIN
+    INSERT INTO filesSearch (fileID, caption, tags, people, location)
+    VALUES (NEW.id, COALESCE(NEW.caption, ''), '', '', COALESCE(NEW.location, ''));
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchUpdate AFTER UPDATE OF caption, location ON files BEGIN
+    UPDATE filesSearch SET caption = COALESCE(NEW.caption, ''), location = COALESCE(NEW.location, '')
+    WHERE fileID = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchDelete AFTER DELETE ON files BEGIN
+    DELETE FROM filesSearch WHERE fileID = OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchTagInsert AFTER INSERT ON tagFile BEGIN
+    UPDATE filesSearch SET tags = COALESCE((SELECT group_concat(tf.tagString, ' ') FROM tagFile tf WHERE tf.fileID = NEW.fileID), '')
+    WHERE fileID = NEW.fileID;
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchTagDelete AFTER DELETE ON tagFile BEGIN
+    UPDATE filesSearch SET tags = COALESCE((SELECT group_concat(tf.tagString, ' ') FROM tagFile tf WHERE tf.fileID = OLD.fileID), '')
+    WHERE fileID = OLD.fileID;
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchPersonInsert AFTER INSERT ON userFile BEGIN
+    UPDATE filesSearch SET people = COALESCE((SELECT group_concat(u.username, ' ') FROM userFile uf INNER JOIN users u ON u.id = uf.userID WHERE uf.fileID = NEW.fileID), '')
+    WHERE fileID = NEW.fileID;
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchPersonDelete AFTER DELETE ON userFile BEGIN
+    UPDATE filesSearch SET people = COALESCE((SELECT group_concat(u.username, ' ') FROM userFile uf INNER JOIN users u ON u.id = uf.userID WHERE uf.fileID = OLD.fileID), '')
+    WHERE fileID = OLD.fileID;
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchRename AFTER UPDATE OF username ON users BEGIN
+    UPDATE filesSearch SET people = COALESCE((SELECT group_concat(u.username, ' ') FROM userFile uf INNER JOIN users u ON u.id = uf.userID WHERE uf.fileID = filesSearch.fileID), '')
+    WHERE fileID IN (SELECT fileID FROM userFile WHERE userID = NEW.id);
+END;
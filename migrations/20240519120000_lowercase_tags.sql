@@ -0,0 +1,8 @@
+-- Tags are lowercased on insert, fold existing ones the same way.
+-- Tags differing only in case are merged.
+INSERT OR IGNORE INTO tags (tagString) SELECT lower(tagString) FROM tags;
+
+UPDATE OR IGNORE tagFile SET tagString = lower(tagString) WHERE tagString != lower(tagString);
+DELETE FROM tagFile WHERE tagString != lower(tagString);
+
+DELETE FROM tags WHERE tagString != lower(tagString);
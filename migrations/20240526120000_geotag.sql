@@ -0,0 +1,5 @@
+-- Where a photo was taken, in decimal degrees
+ALTER TABLE files ADD COLUMN latitude REAL NULL;
+ALTER TABLE files ADD COLUMN longitude REAL NULL;
+
+CREATE INDEX IF NOT EXISTS files_coordinates ON files(latitude, longitude);
@@ -0,0 +1,22 @@
+-- Place a photo was taken, named from its coordinates with the bundled gazetteer
+ALTER TABLE files ADD COLUMN city text NULL;
+ALTER TABLE files ADD COLUMN region text NULL;
+ALTER TABLE files ADD COLUMN country text NULL;
+
+CREATE INDEX IF NOT EXISTS files_place ON files(country, region, city);
+
+-- Place names are searchable together with the free text location
+DROP TRIGGER IF EXISTS filesSearchInsert;
+DROP TRIGGER IF EXISTS filesSearchUpdate;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchInsert AFTER INSERT ON files BEGIN
+    INSERT INTO filesSearch (fileID, caption, tags, people, location)
+    VALUES (NEW.id, COALESCE(NEW.caption, ''), '', '',
+        trim(COALESCE(NEW.location, '') || ' ' || COALESCE(NEW.city, '') || ' ' || COALESCE(NEW.region, '') || ' ' || COALESCE(NEW.country, '')));
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchUpdate AFTER UPDATE OF caption, location, city, region, country ON files BEGIN
+    UPDATE filesSearch SET caption = COALESCE(NEW.caption, ''),
+        location = trim(COALESCE(NEW.location, '') || ' ' || COALESCE(NEW.city, '') || ' ' || COALESCE(NEW.region, '') || ' ' || COALESCE(NEW.country, ''))
+    WHERE fileID = NEW.id;
+END;
@@ -0,0 +1,14 @@
+-- Faces found by the detector that are not yet named. Naming a face moves
+-- its box to userFile.
+CREATE TABLE IF NOT EXISTS faces (
+    id         INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    fileID     uuid NOT NULL,
+    x          INTEGER NOT NULL,
+    y          INTEGER NOT NULL,
+    width      INTEGER NOT NULL,
+    height     INTEGER NOT NULL,
+    confidence REAL NOT NULL,
+    FOREIGN KEY(fileID) REFERENCES files(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS faces_file ON faces(fileID);
@@ -0,0 +1,4 @@
+-- Face recognition embeddings as little endian f32 blobs, kept with named
+-- faces to suggest who unnamed faces are
+ALTER TABLE faces ADD COLUMN embedding BLOB NULL;
+ALTER TABLE userFile ADD COLUMN embedding BLOB NULL;
@@ -0,0 +1,61 @@
+-- People in photos, separate from login accounts. A person can be linked to
+-- the account of the same person.
+CREATE TABLE IF NOT EXISTS people (
+    id      INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    name    TEXT NOT NULL UNIQUE,
+    userID  INTEGER NULL UNIQUE,
+    FOREIGN KEY(userID) REFERENCES users(id) ON DELETE SET NULL
+);
+
+-- Everyone tagged becomes a person with the id of their user, so saved
+-- filters keep working. Only accounts that signed up or have a pending
+-- invite stay linked.
+INSERT INTO people (id, name, userID)
+SELECT u.id, u.username,
+    CASE WHEN u.signed_up = 1 OR EXISTS (SELECT 1 FROM invites i WHERE i.user_id = u.id) THEN u.id END
+FROM users u
+WHERE EXISTS (SELECT 1 FROM userFile uf WHERE uf.userID = u.id);
+
+CREATE TABLE IF NOT EXISTS personFile (
+    personID  INTEGER not null,
+    fileID    uuid not null,
+    x         INTEGER null,
+    y         INTEGER null,
+    width     INTEGER null,
+    height    INTEGER null,
+    embedding BLOB null,
+    PRIMARY KEY(personID, fileID),
+    FOREIGN KEY(personID) REFERENCES people(id) ON DELETE CASCADE,
+    FOREIGN KEY(fileID) REFERENCES files(id) ON DELETE CASCADE
+);
+
+INSERT INTO personFile (personID, fileID, x, y, width, height, embedding)
+SELECT userID, fileID, x, y, width, height, embedding FROM userFile;
+
+-- Search the names of people instead of usernames
+DROP TRIGGER IF EXISTS filesSearchRename;
+DROP TABLE userFile;
+ALTER TABLE personFile RENAME TO userFile;
+
+-- Accounts that were only created by tagging someone
+DELETE FROM users WHERE id IN (SELECT id FROM people WHERE userID IS NULL);
+
+CREATE TRIGGER IF NOT EXISTS filesSearchPersonInsert AFTER INSERT ON userFile BEGIN
+    UPDATE filesSearch SET people = COALESCE((SELECT group_concat(p.name, ' ') FROM userFile uf INNER JOIN people p ON p.id = uf.personID WHERE uf.fileID = NEW.fileID), '')
+    WHERE fileID = NEW.fileID;
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchPersonUpdate AFTER UPDATE OF personID ON userFile BEGIN
+    UPDATE filesSearch SET people = COALESCE((SELECT group_concat(p.name, ' ') FROM userFile uf INNER JOIN people p ON p.id = uf.personID WHERE uf.fileID = NEW.fileID), '')
+    WHERE fileID = NEW.fileID;
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchPersonDelete AFTER DELETE ON userFile BEGIN
+    UPDATE filesSearch SET people = COALESCE((SELECT group_concat(p.name, ' ') FROM userFile uf INNER JOIN people p ON p.id = uf.personID WHERE uf.fileID = OLD.fileID), '')
+    WHERE fileID = OLD.fileID;
+END;
+
+CREATE TRIGGER IF NOT EXISTS filesSearchRename AFTER UPDATE OF name ON people BEGIN
+    UPDATE filesSearch SET people = COALESCE((SELECT group_concat(p.name, ' ') FROM userFile uf INNER JOIN people p ON p.id = uf.personID WHERE uf.fileID = filesSearch.fileID), '')
+    WHERE fileID IN (SELECT fileID FROM userFile WHERE personID = NEW.id);
+END;
@@ -0,0 +1,4 @@
+-- Avatar of a person, a WebP crop of one of their tagged faces. It replaces
+-- the profile picture of accounts, which was never set.
+ALTER TABLE people ADD COLUMN avatar BLOB NULL;
+ALTER TABLE users DROP COLUMN profilePic;
@@ -0,0 +1,9 @@
+-- Renaming, merging and lowercasing tags updates tagFile in place, which the
+-- search index missed.
+CREATE TRIGGER IF NOT EXISTS filesSearchTagUpdate AFTER UPDATE OF tagString ON tagFile BEGIN
+    UPDATE filesSearch SET tags = COALESCE((SELECT group_concat(tf.tagString, ' ') FROM tagFile tf WHERE tf.fileID = NEW.fileID), '')
+    WHERE fileID = NEW.fileID;
+END;
+
+-- Index the tags as they are now
+UPDATE filesSearch SET tags = COALESCE((SELECT group_concat(tf.tagString, ' ') FROM tagFile tf WHERE tf.fileID = filesSearch.fileID), '');
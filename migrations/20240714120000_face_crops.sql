@@ -0,0 +1,3 @@
+-- Small WebP crops of unnamed faces for the review queue, so the originals
+-- are not decoded every time the queue is shown
+ALTER TABLE faces ADD COLUMN crop BLOB NULL;
@@ -0,0 +1,3 @@
+-- Boxes rejected as not being faces are kept, so scanning the photo again
+-- does not bring them back
+ALTER TABLE faces ADD COLUMN dismissed BOOLEAN NOT NULL DEFAULT 0;
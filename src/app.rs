@@ -1,6 +1,8 @@
 use crate::components::{
     login::Login,
     logout::Logout,
+    people::{PeoplePage, PersonPage},
+    share::SharedView,
     signup::Signup,
     //topbar::TopBar
 };
@@ -79,6 +81,11 @@ pub fn App() -> impl IntoView {
         <Root default_theme=LeptonicTheme::default()>
 
         <link rel="stylesheet" href="https://cdnjs.cloudflare.com/ajax/libs/font-awesome/5.15.4/css/all.min.css"/>
+        // Pinned to the hashes published by Leaflet so a changed CDN file is refused
+        <link rel="stylesheet" href="https://unpkg.com/leaflet@1.9.4/dist/leaflet.css"
+            integrity="sha256-p4NxAoJBhIIN+hmNHrzRCf9tD/miZyoHS5obTRR9BMY=" crossorigin=""/>
+        <script src="https://unpkg.com/leaflet@1.9.4/dist/leaflet.js"
+            integrity="sha256-20nQCchB9co0qIjJZRGuk2/Z9VM+kNiyxNV1lvTlZBo=" crossorigin=""></script>
         <Link rel="shortcut icon" type_="image/ico" href="/favicon.ico"/>
         <Stylesheet id="leptos" href="/pkg/photo-album.css"/>
         <Router>
@@ -105,8 +112,11 @@ pub fn App() -> impl IntoView {
                                         let c_user = user.clone();
                                         view! {
                                             <a href="/" class="active">"Home"</a>
+                                            <a href="/people">"People"</a>
+                                            <a href="/faces">"Faces"</a>
                                             <Show when=move || {c_user.has("admin")}>
                                                 <a href="/admin">"Admin"</a>
+                                                <a href="/stats">"Stats"</a>
                                             </Show>
                                             <ActionForm action=logout class="topbarNav-right">
                                                 <button type="submit">"Sign Out"</button>
@@ -152,6 +162,9 @@ pub fn App() -> impl IntoView {
                             }
                         }>
                             <Route path="/" view=HomePage/>
+                            <Route path="/people" view=PeoplePage/>
+                            <Route path="/people/:id" view=PersonPage/>
+                            <Route path="/faces" view=FaceReviewPage/>
                             <Route path="/admin" view=move || {
                                 view! {
                                     <Show
@@ -163,6 +176,17 @@ pub fn App() -> impl IntoView {
                                     </Show>
                                 }
                             }/>
+                            <Route path="/stats" view=move || {
+                                view! {
+                                    <Show
+                                        when=move || {user.get().map(|user| match user {
+                                            Ok(Some(user)) => user.has("admin"),
+                                            _ => false,
+                                        }).unwrap_or(false)}>
+                                        <StatsPage/>
+                                    </Show>
+                                }
+                            }/>
                         </Route>
                     </Route>
                     <Route path="/signup" view=move || {
@@ -179,6 +203,8 @@ pub fn App() -> impl IntoView {
                     }>
                         <Route path=":invite" view=move || view! { <Signup action=signup/> }/>
                     </Route>
+                    // Anonymous, read-only access through share links
+                    <Route path="/share/:token" view=SharedView/>
 
                     <Route path="*any" view=move || view! { <h1>"Not Found"</h1> }/>
                 </Routes>
@@ -209,13 +235,39 @@ fn Loading() -> impl IntoView {
     }
 }
 
+#[component]
+fn FaceReviewPage() -> impl IntoView {
+    use crate::components::faces::FaceReviewPage;
+
+    view! {
+        <FaceReviewPage/>
+    }
+}
+
+#[component]
+fn StatsPage() -> impl IntoView {
+    use crate::components::stats::StatsPage;
+
+    view! {
+        <StatsPage/>
+    }
+}
+
 #[component]
 fn AdminPanel() -> impl IntoView {
     // todo:: Probably rename to User Manager or something
+    use crate::components::faces::FaceScanPanel;
     use crate::components::invite::InvitePanel;
+    use crate::components::people::PeoplePanel;
+    use crate::components::share::SharePanel;
+    use crate::components::tags::TagPanel;
 
     view! {
         <h1>Admin panel</h1>
         <InvitePanel/>
+        <SharePanel/>
+        <TagPanel/>
+        <PeoplePanel/>
+        <FaceScanPanel/>
     }
 }
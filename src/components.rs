@@ -1,4 +1,7 @@
+pub mod albums;
+pub mod bulk;
 pub mod dialog;
+pub mod faces;
 pub mod feed;
 pub mod home_page;
 pub mod image_view;
@@ -6,7 +9,14 @@ pub mod invite;
 pub mod loading;
 pub mod login;
 pub mod logout;
+pub mod map;
+pub mod memories;
+pub mod people;
+pub mod saved_filters;
+pub mod share;
 pub mod signup;
+pub mod stats;
+pub mod tags;
 pub mod topbar;
 pub mod upload;
 pub mod users;
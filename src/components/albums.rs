@@ -0,0 +1,902 @@
+#[cfg(feature = "ssr")]
+use crate::auth;
+use crate::components::share::ShareLinks;
+use crate::components::users::{get_account_list, UserInfo};
+use leptos::html::{Input, Select};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+pub const ROLE_OWNER: &str = "owner";
+pub const ROLE_CONTRIBUTOR: &str = "contributor";
+pub const ROLE_VIEWER: &str = "viewer";
+//Longest side of the cover shown next to an album
+#[cfg(feature = "ssr")]
+const COVER_SIZE: u32 = 160;
+
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd, Clone, Serialize, Deserialize)]
+pub struct Album {
+    pub id: String,
+    pub name: String,
+    pub owner: String,
+    pub private: bool,
+    /// Id of the cover photo, if one has been picked
+    pub cover: Option<String>,
+    /// Role of the requesting user. Empty for public albums the user is not a member of.
+    pub role: String,
+}
+
+impl Album {
+    pub fn can_contribute(&self) -> bool {
+        self.role == ROLE_OWNER || self.role == ROLE_CONTRIBUTOR
+    }
+}
+
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AlbumMember {
+    pub user_id: i64,
+    pub username: String,
+    pub role: String,
+}
+
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AlbumPhoto {
+    pub id: String,
+    pub data: String,
+    pub cover: bool,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{ROLE_CONTRIBUTOR, ROLE_OWNER};
+    use crate::auth::User;
+    use leptos::ServerFnError;
+    use sqlx::{Sqlite, SqlitePool, Transaction};
+
+    /// Role the user holds in the album. `None` if the album is hidden from the user, and an
+    /// empty string if the album is public and the user is not a member.
+    pub async fn album_role(
+        album_id: &str,
+        user_id: i64,
+        pool: &SqlitePool,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT CASE WHEN a.ownerId = ? THEN 'owner' ELSE COALESCE(aa.role, '') END
+            FROM albums a
+            LEFT JOIN albumAccess aa ON aa.albumID = a.id AND aa.userID = ?
+            WHERE a.id = ? AND (a.private = 0 OR a.ownerId = ? OR aa.userID IS NOT NULL);",
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .bind(album_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+    }
+
+    pub async fn require_contributor(
+        album_id: &str,
+        user_id: i64,
+        pool: &SqlitePool,
+    ) -> Result<(), ServerFnError> {
+        match album_role(album_id, user_id, pool).await?.as_deref() {
+            Some(ROLE_OWNER) | Some(ROLE_CONTRIBUTOR) => Ok(()),
+            _ => Err(ServerFnError::ServerError(
+                "You are not allowed to change this album".to_string(),
+            )),
+        }
+    }
+
+    pub async fn require_owner(
+        album_id: &str,
+        user_id: i64,
+        pool: &SqlitePool,
+    ) -> Result<(), ServerFnError> {
+        match album_role(album_id, user_id, pool).await?.as_deref() {
+            Some(ROLE_OWNER) => Ok(()),
+            _ => Err(ServerFnError::ServerError(
+                "Only the owner can manage this album".to_string(),
+            )),
+        }
+    }
+
+    /// Private albums hide their photos from everyone else, so only the uploader of a photo or
+    /// an admin may put it in one.
+    pub async fn require_can_hide(
+        album_id: &str,
+        file_id: &str,
+        user: &User,
+        pool: &SqlitePool,
+    ) -> Result<(), ServerFnError> {
+        if user.has("admin") {
+            return Ok(());
+        }
+
+        let allowed: Option<bool> = sqlx::query_scalar(
+            "SELECT a.private = 0 OR f.uploadedBy = ? FROM albums a, files f
+            WHERE a.id = ? AND f.id = ?",
+        )
+        .bind(user.id)
+        .bind(album_id)
+        .bind(file_id)
+        .fetch_optional(pool)
+        .await?;
+
+        match allowed {
+            Some(true) => Ok(()),
+            _ => Err(ServerFnError::ServerError(
+                "Only the uploader can add a photo to a private album".to_string(),
+            )),
+        }
+    }
+
+    /// Making an album private hides its photos, so unless the user is an admin it may only
+    /// hold photos they uploaded.
+    pub async fn require_can_make_private(
+        album_id: &str,
+        user: &User,
+        pool: &SqlitePool,
+    ) -> Result<(), ServerFnError> {
+        if user.has("admin") {
+            return Ok(());
+        }
+
+        let others: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM albumFile af INNER JOIN files f ON f.id = af.fileID
+            WHERE af.albumID = ? AND f.uploadedBy != ?)",
+        )
+        .bind(album_id)
+        .bind(user.id)
+        .fetch_one(pool)
+        .await?;
+
+        if others {
+            return Err(ServerFnError::ServerError(
+                "Only albums with your own photos can be made private".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Base64 WebP thumbnail of the cover of an album, if it has one the user can see.
+    pub async fn cover(
+        album_id: &str,
+        user_id: i64,
+        pool: &SqlitePool,
+    ) -> Result<Option<String>, ServerFnError> {
+        use crate::image_filter::image_filter;
+
+        let (access, access_binds) = image_filter::access_condition(user_id);
+        let query = format!(
+            "SELECT f.path FROM albums a INNER JOIN files f ON f.id = a.coverID
+            WHERE a.id = ? AND {}",
+            access
+        );
+        let mut query = sqlx::query_scalar::<_, String>(&query).bind(album_id);
+        for bind in access_binds {
+            query = query.bind(bind);
+        }
+        let Some(path) = query.fetch_optional(pool).await? else {
+            return Ok(None);
+        };
+
+        let thumbnail = tokio::task::spawn_blocking(move || {
+            let mut buf = Vec::new();
+            image::open(path)?
+                .thumbnail(super::COVER_SIZE, super::COVER_SIZE)
+                .write_to(
+                    &mut std::io::Cursor::new(&mut buf),
+                    image::ImageFormat::WebP,
+                )?;
+            Ok::<_, image::ImageError>(buf)
+        })
+        .await??;
+
+        Ok(Some(base64::encode(thumbnail)))
+    }
+
+    /// Add a file to the end of an album. Does nothing if it is already there.
+    pub async fn add_file<'e, E>(
+        album_id: &str,
+        file_id: &str,
+        executor: E,
+    ) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        sqlx::query(
+            "INSERT OR IGNORE INTO albumFile (albumID, fileID, position)
+            SELECT ?, ?, COALESCE(MAX(position) + 1, 0) FROM albumFile WHERE albumID = ?",
+        )
+        .bind(album_id)
+        .bind(file_id)
+        .bind(album_id)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Take a file out of an album, and drop it as the cover.
+    pub async fn remove_file(
+        album_id: &str,
+        file_id: &str,
+        tx: &mut Transaction<'_, Sqlite>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM albumFile WHERE albumID = ? AND fileID = ?")
+            .bind(album_id)
+            .bind(file_id)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("UPDATE albums SET coverID = NULL WHERE id = ? AND coverID = ?")
+            .bind(album_id)
+            .bind(file_id)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(())
+    }
+}
+
+//Albums visible to the current user
+#[server(GetAlbums, "/api")]
+pub async fn get_albums() -> Result<Vec<Album>, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let albums = sqlx::query_as::<_, Album>(
+        "SELECT a.id, a.name, u.username AS owner, a.private, a.coverID AS cover,
+            CASE WHEN a.ownerId = ? THEN 'owner' ELSE COALESCE(aa.role, '') END AS role
+        FROM albums a
+        INNER JOIN users u ON u.id = a.ownerId
+        LEFT JOIN albumAccess aa ON aa.albumID = a.id AND aa.userID = ?
+        WHERE a.private = 0 OR a.ownerId = ? OR aa.userID IS NOT NULL
+        ORDER BY a.name;",
+    )
+    .bind(user.id)
+    .bind(user.id)
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(albums)
+}
+
+#[server(CreateAlbum, "/api")]
+pub async fn create_album(name: String, private: bool) -> Result<String, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    use uuid::Uuid;
+    let pool = pool()?;
+
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ServerFnError::ServerError(
+            "The album needs a name".to_string(),
+        ));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(
+        "INSERT INTO albums (id, name, ownerId, private, createdDate)
+        VALUES (?, ?, ?, ?, datetime('now', 'localtime'))",
+    )
+    .bind(&id)
+    .bind(name)
+    .bind(user.id)
+    .bind(private)
+    .execute(&pool)
+    .await?;
+
+    Ok(id)
+}
+
+#[server(DeleteAlbum, "/api")]
+pub async fn delete_album(album_id: String) -> Result<(), ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    ssr::require_owner(&album_id, user.id, &pool).await?;
+
+    // The photos themselves are kept, only the album and its memberships are removed
+    sqlx::query("DELETE FROM albums WHERE id = ?")
+        .bind(album_id)
+        .execute(&pool)
+        .await?;
+
+    Ok(())
+}
+
+#[server(SetAlbumPrivate, "/api")]
+pub async fn set_album_private(album_id: String, private: bool) -> Result<(), ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    ssr::require_owner(&album_id, user.id, &pool).await?;
+    if private {
+        ssr::require_can_make_private(&album_id, &user, &pool).await?;
+    }
+
+    sqlx::query("UPDATE albums SET private = ? WHERE id = ?")
+        .bind(private)
+        .bind(album_id)
+        .execute(&pool)
+        .await?;
+
+    Ok(())
+}
+
+#[server(GetAlbumMembers, "/api")]
+pub async fn get_album_members(album_id: String) -> Result<Vec<AlbumMember>, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    ssr::require_owner(&album_id, user.id, &pool).await?;
+
+    let members = sqlx::query_as::<_, AlbumMember>(
+        "SELECT aa.userID AS user_id, u.username, aa.role
+        FROM albumAccess aa
+        INNER JOIN users u ON u.id = aa.userID
+        WHERE aa.albumID = ?
+        ORDER BY u.username;",
+    )
+    .bind(album_id)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(members)
+}
+
+//Grant, change or revoke (role = None) a user's access to an album
+#[server(SetAlbumMember, "/api")]
+pub async fn set_album_member(
+    album_id: String,
+    user_id: i64,
+    role: Option<String>,
+) -> Result<(), ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    ssr::require_owner(&album_id, user.id, &pool).await?;
+
+    match role {
+        None => {
+            sqlx::query("DELETE FROM albumAccess WHERE albumID = ? AND userID = ?")
+                .bind(album_id)
+                .bind(user_id)
+                .execute(&pool)
+                .await?;
+        }
+        Some(role) => {
+            if role != ROLE_VIEWER && role != ROLE_CONTRIBUTOR {
+                return Err(ServerFnError::ServerError(format!(
+                    "Unknown album role {}",
+                    role
+                )));
+            }
+
+            sqlx::query(
+                "INSERT OR REPLACE INTO albumAccess (albumID, userID, role) VALUES (?, ?, ?)",
+            )
+            .bind(album_id)
+            .bind(user_id)
+            .bind(role)
+            .execute(&pool)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+//Albums an image is part of, limited to those the user can see
+#[server(GetFileAlbums, "/api")]
+pub async fn get_file_albums(file_id: String) -> Result<Vec<Album>, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let albums = sqlx::query_as::<_, Album>(
+        "SELECT a.id, a.name, u.username AS owner, a.private, a.coverID AS cover,
+            CASE WHEN a.ownerId = ? THEN 'owner' ELSE COALESCE(aa.role, '') END AS role
+        FROM albumFile af
+        INNER JOIN albums a ON a.id = af.albumID
+        INNER JOIN users u ON u.id = a.ownerId
+        LEFT JOIN albumAccess aa ON aa.albumID = a.id AND aa.userID = ?
+        WHERE af.fileID = ? AND (a.private = 0 OR a.ownerId = ? OR aa.userID IS NOT NULL)
+        ORDER BY a.name;",
+    )
+    .bind(user.id)
+    .bind(user.id)
+    .bind(file_id)
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(albums)
+}
+
+#[server(AlbumCover, "/api")]
+pub async fn get_album_cover(album_id: String) -> Result<Option<String>, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    if ssr::album_role(&album_id, user.id, &pool).await?.is_none() {
+        return Err(ServerFnError::ServerError(
+            "You are not authorized to view this album".to_string(),
+        ));
+    }
+
+    ssr::cover(&album_id, user.id, &pool).await
+}
+
+#[server(AddToAlbum, "/api")]
+pub async fn add_to_album(album_id: String, file_id: String) -> Result<(), ServerFnError> {
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    ssr::require_contributor(&album_id, user.id, &pool).await?;
+    if !image_filter::can_view_file(&file_id, user.id, &pool).await? {
+        return Err(ServerFnError::ServerError(
+            "You are not authorized to view this image".to_string(),
+        ));
+    }
+    ssr::require_can_hide(&album_id, &file_id, &user, &pool).await?;
+
+    ssr::add_file(&album_id, &file_id, &pool).await?;
+
+    Ok(())
+}
+
+#[server(RemoveFromAlbum, "/api")]
+pub async fn remove_from_album(album_id: String, file_id: String) -> Result<(), ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    ssr::require_contributor(&album_id, user.id, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+    ssr::remove_file(&album_id, &file_id, &mut tx).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+//Photos of an album in manual order, for arranging it
+#[server(GetAlbumPhotos, "/api")]
+pub async fn get_album_photos(album_id: String) -> Result<Vec<AlbumPhoto>, ServerFnError> {
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    use std::fs;
+    let pool = pool()?;
+
+    if ssr::album_role(&album_id, user.id, &pool).await?.is_none() {
+        return Err(ServerFnError::ServerError(
+            "You are not authorized to view this album".to_string(),
+        ));
+    }
+
+    let (access, access_binds) = image_filter::access_condition(user.id);
+    let query = format!(
+        "SELECT f.id, f.path AS data, f.id IS a.coverID AS cover FROM albumFile af
+        INNER JOIN files f ON f.id = af.fileID
+        INNER JOIN albums a ON a.id = af.albumID
+        WHERE af.albumID = ? AND {}
+        ORDER BY af.position, f.uploadDate DESC;",
+        access
+    );
+
+    let mut query = sqlx::query_as::<_, AlbumPhoto>(&query).bind(album_id);
+    for bind in access_binds {
+        query = query.bind(bind);
+    }
+
+    let mut photos = query.fetch_all(&pool).await?;
+    for photo in &mut photos {
+        photo.data = base64::encode(fs::read(&photo.data)?);
+    }
+
+    Ok(photos)
+}
+
+//Pick the cover photo of an album, or clear it
+#[server(SetAlbumCover, "/api")]
+pub async fn set_album_cover(
+    album_id: String,
+    file_id: Option<String>,
+) -> Result<(), ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    ssr::require_contributor(&album_id, user.id, &pool).await?;
+
+    let res = sqlx::query(
+        "UPDATE albums SET coverID = ?1 WHERE id = ?2
+        AND (?1 IS NULL OR EXISTS (SELECT 1 FROM albumFile WHERE albumID = ?2 AND fileID = ?1))",
+    )
+    .bind(file_id)
+    .bind(album_id)
+    .execute(&pool)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(ServerFnError::ServerError(
+            "The cover has to be a photo in the album".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+//Store a manual order for an album. Photos left out keep their relative order after the listed ones.
+#[server(ReorderAlbum, "/api")]
+pub async fn reorder_album(album_id: String, file_ids: Vec<String>) -> Result<(), ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    ssr::require_contributor(&album_id, user.id, &pool).await?;
+
+    let mut tx = pool.begin().await?;
+
+    // Move everything behind the new order first
+    sqlx::query("UPDATE albumFile SET position = position + ? WHERE albumID = ?")
+        .bind(file_ids.len() as i64)
+        .bind(&album_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for (position, file_id) in file_ids.iter().enumerate() {
+        sqlx::query("UPDATE albumFile SET position = ? WHERE albumID = ? AND fileID = ?")
+            .bind(position as i64)
+            .bind(&album_id)
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+//Create albums and manage who has access to them
+#[component]
+pub fn AlbumManager(#[prop(into)] on_change: Callback<()>) -> impl IntoView {
+    let albums = create_resource(|| (), |_| async { get_albums().await });
+    let accounts = create_resource(|| (), |_| async { get_account_list().await });
+    let (error, set_error) = create_signal(None::<String>);
+
+    let name_input = create_node_ref::<Input>();
+    let private_input = create_node_ref::<Input>();
+
+    let create = move |_| {
+        let name = name_input.get_untracked().unwrap().value();
+        let private = private_input.get_untracked().unwrap().checked();
+        spawn_local(async move {
+            match create_album(name, private).await {
+                Ok(_) => {
+                    set_error(None);
+                    name_input.get_untracked().unwrap().set_value("");
+                    albums.refetch();
+                    on_change(());
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+
+    view! {
+        <div class="horizontal">
+            <input type="text" placeholder="New album name" _ref=name_input/>
+            <label><input type="checkbox" _ref=private_input/>"Private"</label>
+            <button on:click=create>"Create album"</button>
+        </div>
+        <Show when=move || error().is_some()>
+            <span>{error().unwrap()}</span>
+        </Show>
+        <Suspense fallback=move || view! {<p>"Loading albums"</p>}>
+            <ErrorBoundary fallback=|_| {view! {<p>"Something went wrong"</p>}}>
+                {move || albums.get().map(|res| res.map(|albums| {
+                    albums.into_iter()
+                        .filter(|album| album.role == ROLE_OWNER)
+                        .map(|album| view! {
+                            <AlbumAccessEditor
+                                album=album
+                                accounts=accounts
+                                on_change=move |_| {
+                                    albums.refetch();
+                                    on_change(());
+                                }/>
+                        })
+                        .collect_view()
+                }))}
+            </ErrorBoundary>
+        </Suspense>
+    }
+}
+
+#[component]
+fn AlbumAccessEditor(
+    album: Album,
+    accounts: Resource<(), Result<Vec<UserInfo>, ServerFnError>>,
+    #[prop(into)] on_change: Callback<()>,
+) -> impl IntoView {
+    let album_id = store_value(album.id.clone());
+    let members = create_resource(
+        move || album_id.get_value(),
+        |album_id| async move { get_album_members(album_id).await.unwrap_or_default() },
+    );
+
+    let user_select = create_node_ref::<Select>();
+    let role_select = create_node_ref::<Select>();
+
+    let add_member = move |_| {
+        let user_id = user_select.get_untracked().unwrap().value().parse::<i64>();
+        let role = role_select.get_untracked().unwrap().value();
+        let Ok(user_id) = user_id else {
+            return;
+        };
+        spawn_local(async move {
+            if set_album_member(album_id.get_value(), user_id, Some(role))
+                .await
+                .is_ok()
+            {
+                members.refetch();
+            }
+        });
+    };
+
+    let remove_member = move |user_id: i64| {
+        spawn_local(async move {
+            if set_album_member(album_id.get_value(), user_id, None)
+                .await
+                .is_ok()
+            {
+                members.refetch();
+            }
+        });
+    };
+
+    let has_cover = album.cover.is_some();
+    let cover = create_resource(
+        move || album_id.get_value(),
+        move |album_id| async move {
+            if !has_cover {
+                return None;
+            }
+            get_album_cover(album_id).await.ok().flatten()
+        },
+    );
+
+    let private = album.private;
+    let (error, set_error) = create_signal(None::<String>);
+    let toggle_private = move |_| {
+        spawn_local(async move {
+            match set_album_private(album_id.get_value(), !private).await {
+                Ok(_) => on_change(()),
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+
+    let (sharing, set_sharing) = create_signal(false);
+
+    let remove_album = move |_| {
+        spawn_local(async move {
+            if delete_album(album_id.get_value()).await.is_ok() {
+                on_change(());
+            }
+        });
+    };
+
+    view! {
+        <div class="user-item album-item">
+            <Suspense fallback=|| ()>
+                {move || cover.get().flatten().map(|data| view! {
+                    <img class="album-cover" src=format!("data:image/webp;base64,{}", data)/>
+                })}
+            </Suspense>
+            <p>{album.name}{if private {" (private)"} else {""}}</p>
+            <div class="buttons">
+                <button on:click=toggle_private>{if private {"Make public"} else {"Make private"}}</button>
+                <button on:click=move |_| set_sharing.update(|s| *s = !*s)>"Share"</button>
+                <button on:click=remove_album>"Delete album"</button>
+            </div>
+            <Show when=move || error().is_some()>
+                <span>{move || error().unwrap_or_default()}</span>
+            </Show>
+        </div>
+        <Show when=sharing>
+            <ShareLinks file_id=None album_id=Some(album_id.get_value())/>
+        </Show>
+        <div class="album-members">
+            {move || members.get().unwrap_or_default().into_iter().map(|member| {
+                let user_id = member.user_id;
+                view! {
+                    <div class="user-item">
+                        <p>{member.username}" - "{member.role}</p>
+                        <button on:click=move |_| remove_member(user_id)>"Remove"</button>
+                    </div>
+                }
+            }).collect_view()}
+            <div class="horizontal">
+                <select _ref=user_select>
+                    {move || accounts.get().and_then(|res| res.ok()).unwrap_or_default().into_iter().map(|account| view! {
+                        <option value={account.id.to_string()}>{account.username}</option>
+                    }).collect_view()}
+                </select>
+                <select _ref=role_select>
+                    <option value=ROLE_VIEWER>"Viewer"</option>
+                    <option value=ROLE_CONTRIBUTOR>"Contributor"</option>
+                </select>
+                <button on:click=add_member>"Give access"</button>
+            </div>
+        </div>
+    }
+}
+
+//Show and edit which albums an image belongs to
+#[component]
+pub fn ImageAlbums<W>(image_id: W) -> impl IntoView
+where
+    W: Fn() -> String + Copy + 'static,
+{
+    let albums = create_resource(|| (), |_| async { get_albums().await.unwrap_or_default() });
+    let file_albums = create_resource(image_id, |image_id| async move {
+        if image_id.is_empty() {
+            return Vec::new();
+        }
+        get_file_albums(image_id).await.unwrap_or_default()
+    });
+
+    let album_select = create_node_ref::<Select>();
+
+    let add = move |_| {
+        let album_id = album_select.get_untracked().unwrap().value();
+        if album_id.is_empty() {
+            return;
+        }
+        spawn_local(async move {
+            if add_to_album(album_id, image_id()).await.is_ok() {
+                file_albums.refetch();
+            }
+        });
+    };
+
+    let remove = move |album_id: String| {
+        spawn_local(async move {
+            if remove_from_album(album_id, image_id()).await.is_ok() {
+                file_albums.refetch();
+            }
+        });
+    };
+
+    view! {
+        <div class="upload-info">
+            <h3>"Albums:"</h3>
+            {move || file_albums.get().unwrap_or_default().into_iter().map(|album| {
+                let album_id = album.id.clone();
+                let can_contribute = album.can_contribute();
+                view! {
+                    <span>{album.name}
+                        <Show when=move || can_contribute>
+                            <button on:click={
+                                let album_id = album_id.clone();
+                                move |_| remove(album_id.clone())
+                            }>"Remove"</button>
+                        </Show>
+                    </span>
+                }
+            }).collect_view()}
+            <div class="horizontal">
+                <select _ref=album_select>
+                    <option value="">"Add to album..."</option>
+                    {move || albums.get().unwrap_or_default().into_iter()
+                        .filter(|album| album.can_contribute())
+                        .map(|album| view! {
+                            <option value={album.id}>{album.name}</option>
+                        }).collect_view()}
+                </select>
+                <button on:click=add>"Add"</button>
+            </div>
+        </div>
+    }
+}
+
+//Rearrange the photos of an album and pick its cover
+#[component]
+pub fn AlbumArranger(album_id: String, #[prop(into)] on_change: Callback<()>) -> impl IntoView {
+    let album_id = store_value(album_id);
+    let photos = create_rw_signal(Vec::<AlbumPhoto>::new());
+    let (error, set_error) = create_signal(None::<String>);
+
+    spawn_local(async move {
+        match get_album_photos(album_id.get_value()).await {
+            Ok(p) => photos.set(p),
+            Err(e) => set_error(Some(e.to_string())),
+        }
+    });
+
+    //Swap a photo with its neighbour
+    let shift = move |index: usize, up: bool| {
+        photos.update(|photos| {
+            let other = if up {
+                index.checked_sub(1)
+            } else {
+                Some(index + 1)
+            };
+            if let Some(other) = other.filter(|other| *other < photos.len()) {
+                photos.swap(index, other);
+            }
+        });
+    };
+
+    let make_cover = move |file_id: String| {
+        spawn_local(async move {
+            match set_album_cover(album_id.get_value(), Some(file_id.clone())).await {
+                Ok(_) => photos.update(|photos| {
+                    for photo in photos {
+                        photo.cover = photo.id == file_id;
+                    }
+                }),
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+
+    let save = move |_| {
+        let file_ids = photos
+            .get_untracked()
+            .into_iter()
+            .map(|photo| photo.id)
+            .collect();
+        spawn_local(async move {
+            match reorder_album(album_id.get_value(), file_ids).await {
+                Ok(_) => {
+                    set_error(None);
+                    on_change(());
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+
+    view! {
+        <div class="album-arranger">
+            {move || photos.get().into_iter().enumerate().map(|(index, photo)| {
+                let id = photo.id.clone();
+                view! {
+                    <div class="arrange-item" class:cover=photo.cover>
+                        <img src={format!("data:image/jpeg;base64,{}", photo.data)}/>
+                        <div class="horizontal">
+                            <button on:click=move |_| shift(index, true)>"<"</button>
+                            <button on:click=move |_| make_cover(id.clone())>
+                                {if photo.cover {"Cover"} else {"Make cover"}}
+                            </button>
+                            <button on:click=move |_| shift(index, false)>">"</button>
+                        </div>
+                    </div>
+                }
+            }).collect_view()}
+        </div>
+        <Show when=move || error().is_some()>
+            <span>{error().unwrap()}</span>
+        </Show>
+        <button on:click=save>"Save order"</button>
+    }
+}
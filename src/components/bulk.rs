@@ -0,0 +1,296 @@
+#[cfg(feature = "ssr")]
+use crate::auth;
+use crate::auth::User;
+use crate::components::albums::get_albums;
+use crate::components::faces::start_face_scan;
+use crate::components::people::{get_people, PersonInfo};
+use leptos::html::{Input, Select};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+//Action applied to every selected image
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BulkAction {
+    AddTags(Vec<String>),
+    RemoveTags(Vec<String>),
+    AddPeople(Vec<i64>),
+    SetCreatedDate(Option<String>),
+    SetLocation(Option<String>),
+    /// Add to `album`, and remove from `from` if set
+    MoveToAlbum {
+        album: String,
+        from: Option<String>,
+    },
+    Delete,
+}
+
+//Apply an action to many images in one transaction. Nothing is changed if the user
+//may not apply it to every one of them.
+#[server(BulkEdit, "/api")]
+pub async fn bulk_edit(file_ids: Vec<String>, action: BulkAction) -> Result<(), ServerFnError> {
+    use crate::components::albums::ssr::{require_can_hide, require_contributor};
+    use crate::components::tags::ssr::attach_tag;
+    use crate::image_filter::image_filter;
+    use std::fs;
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    for file_id in &file_ids {
+        if !image_filter::can_view_file(file_id, user.id, &pool).await? {
+            return Err(ServerFnError::ServerError(
+                "You are not authorized to view one of the images".to_string(),
+            ));
+        }
+    }
+
+    //Same rule as editing or deleting a single image: only uploader or admin
+    let uploader_only = matches!(
+        action,
+        BulkAction::SetCreatedDate(_) | BulkAction::SetLocation(_) | BulkAction::Delete
+    );
+    if uploader_only && !user.has("admin") {
+        for file_id in &file_ids {
+            let uploader: bool = sqlx::query_scalar("SELECT uploadedBy=? FROM files WHERE id = ?")
+                .bind(user.id)
+                .bind(file_id)
+                .fetch_one(&pool)
+                .await?;
+            if !uploader {
+                return Err(ServerFnError::ServerError(
+                    "You are not authorized, only the uploader can change an image".to_string(),
+                ));
+            }
+        }
+    }
+
+    if let BulkAction::SetCreatedDate(Some(date)) = &action {
+        use regex::Regex;
+        let valid_date = Regex::new(r"^\d{4}-(0[1-9]|1[012])-(0[1-9]|[12][0-9]|3[01])$")
+            .unwrap()
+            .is_match(date);
+        if !valid_date {
+            return Err(ServerFnError::ServerError(
+                "The date is corrupted. The date must have the format yyyy-mm-dd".to_string(),
+            ));
+        }
+    }
+
+    if let BulkAction::MoveToAlbum { album, from } = &action {
+        require_contributor(album, user.id, &pool).await?;
+        if let Some(from) = from {
+            require_contributor(from, user.id, &pool).await?;
+        }
+        for file_id in &file_ids {
+            require_can_hide(album, file_id, &user, &pool).await?;
+        }
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut paths: Vec<String> = Vec::new();
+
+    for file_id in &file_ids {
+        match &action {
+            BulkAction::AddTags(tags) => {
+                for tag in tags {
+                    attach_tag(file_id, tag, &mut *tx).await?;
+                }
+            }
+            BulkAction::RemoveTags(tags) => {
+                //Tags stored before they were normalized are matched as they are
+                for tag in tags {
+                    let normalized = crate::components::tags::normalize_tag(tag);
+                    sqlx::query("DELETE FROM tagFile WHERE tagString IN (?, ?) AND fileID = ?")
+                        .bind(tag)
+                        .bind(normalized)
+                        .bind(file_id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+            BulkAction::AddPeople(people) => {
+                for person in people {
+                    sqlx::query("INSERT OR IGNORE INTO userFile (personID, fileID) VALUES (?, ?)")
+                        .bind(person)
+                        .bind(file_id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+            BulkAction::SetCreatedDate(date) => {
+                sqlx::query("UPDATE files SET createdDate = ? WHERE id = ?")
+                    .bind(date)
+                    .bind(file_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            BulkAction::SetLocation(location) => {
+                sqlx::query("UPDATE files SET location = ? WHERE id = ?")
+                    .bind(location)
+                    .bind(file_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            BulkAction::MoveToAlbum { album, from } => {
+                crate::components::albums::ssr::add_file(album, file_id, &mut *tx).await?;
+                if let Some(from) = from.as_ref().filter(|from| *from != album) {
+                    crate::components::albums::ssr::remove_file(from, file_id, &mut tx).await?;
+                }
+            }
+            BulkAction::Delete => {
+                let path: String = sqlx::query_scalar("SELECT path FROM files WHERE id = ?")
+                    .bind(file_id)
+                    .fetch_one(&mut *tx)
+                    .await?;
+                sqlx::query("DELETE FROM files WHERE id = ?;")
+                    .bind(file_id)
+                    .execute(&mut *tx)
+                    .await?;
+                paths.push(path);
+            }
+        }
+    }
+
+    tx.commit().await?;
+
+    //Delete from file system once the database agrees, the rows are gone either way
+    for path in paths {
+        if let Err(e) = fs::remove_file(&path) {
+            eprintln!("Could not delete {}: {}", path, e);
+        }
+    }
+
+    Ok(())
+}
+
+//Actions for the images selected in the feed
+#[component]
+pub fn BulkActions(
+    selected: RwSignal<HashSet<String>>,
+    /// Album being browsed, images moved to another album are removed from it
+    album: Signal<Option<String>>,
+    #[prop(into)] on_done: Callback<()>,
+) -> impl IntoView {
+    let albums = create_resource(|| (), |_| async { get_albums().await.unwrap_or_default() });
+    let people = create_resource(|| (), |_| async { get_people().await.unwrap_or_default() });
+    let (error, set_error) = create_signal(None::<String>);
+    let (working, set_working) = create_signal(false);
+    let (delete_prompt, set_delete_prompt) = create_signal(false);
+
+    let tags_ref = create_node_ref::<Input>();
+    let person_ref = create_node_ref::<Select>();
+    let date_ref = create_node_ref::<Input>();
+    let location_ref = create_node_ref::<Input>();
+    let album_ref = create_node_ref::<Select>();
+
+    let apply = move |action: BulkAction| {
+        let file_ids: Vec<String> = selected.get_untracked().into_iter().collect();
+        if file_ids.is_empty() {
+            return;
+        }
+        set_working(true);
+        spawn_local(async move {
+            match bulk_edit(file_ids, action).await {
+                Ok(_) => {
+                    set_error(None);
+                    selected.set(HashSet::new());
+                    on_done(());
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+            set_working(false);
+        });
+    };
+
+    //Comma separated list from the tag input
+    let input_tags = move || -> Vec<String> {
+        tags_ref
+            .get_untracked()
+            .unwrap()
+            .value()
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    };
+
+    //Admins can look for faces again in the selected photos
+    let is_admin = use_context::<User>().is_some_and(|user| user.has("admin"));
+    let scan_faces = move |_| {
+        let file_ids: Vec<String> = selected.get_untracked().into_iter().collect();
+        if file_ids.is_empty() {
+            return;
+        }
+        spawn_local(async move {
+            match start_face_scan(file_ids).await {
+                Ok(n) => set_error(Some(format!(
+                    "Scanning {} photos for faces, follow it in the admin panel",
+                    n
+                ))),
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+
+    let optional = |value: String| if value.is_empty() { None } else { Some(value) };
+
+    view! {
+        <div class="bulk-actions horizontal">
+            <span>{move || format!("{} selected", selected.get().len())}</span>
+            <input type="text" placeholder="tag, other/tag" _ref=tags_ref/>
+            <button disabled=working on:click=move |_| apply(BulkAction::AddTags(input_tags()))>"Add tags"</button>
+            <button disabled=working on:click=move |_| apply(BulkAction::RemoveTags(input_tags()))>"Remove tags"</button>
+            <select _ref=person_ref>
+                {move || people.get().unwrap_or_default().into_iter().map(|person: PersonInfo| view! {
+                    <option value={person.id.to_string()}>{person.name}</option>
+                }).collect_view()}
+            </select>
+            <button disabled=working on:click=move |_| {
+                if let Ok(id) = person_ref.get_untracked().unwrap().value().parse::<i64>() {
+                    apply(BulkAction::AddPeople(vec![id]));
+                }
+            }>"Add person"</button>
+            <input type="date" _ref=date_ref/>
+            <button disabled=working on:click=move |_| {
+                apply(BulkAction::SetCreatedDate(optional(date_ref.get_untracked().unwrap().value())))
+            }>"Set date"</button>
+            <input type="text" placeholder="Location" _ref=location_ref/>
+            <button disabled=working on:click=move |_| {
+                apply(BulkAction::SetLocation(optional(location_ref.get_untracked().unwrap().value())))
+            }>"Set location"</button>
+            <select _ref=album_ref>
+                {move || albums.get().unwrap_or_default().into_iter()
+                    .filter(|album| album.can_contribute())
+                    .map(|album| view! {
+                        <option value={album.id}>{album.name}</option>
+                    }).collect_view()}
+            </select>
+            <button disabled=working on:click=move |_| {
+                let target = album_ref.get_untracked().unwrap().value();
+                if !target.is_empty() {
+                    apply(BulkAction::MoveToAlbum { album: target, from: album.get_untracked() });
+                }
+            }>"Move to album"</button>
+            <Show
+                when=delete_prompt
+                fallback=move || view! {
+                    <button disabled=working on:click=move |_| set_delete_prompt(true)>"Delete"</button>
+                }>
+                <button
+                    style="background-color: red;"
+                    on:click=move |_| {
+                        set_delete_prompt(false);
+                        apply(BulkAction::Delete);
+                    }>{move || format!("Delete {} photos", selected.get().len())}</button>
+                <button style="background-color: gray;" on:click=move |_| set_delete_prompt(false)>"Cancel"</button>
+            </Show>
+            <Show when=move || is_admin>
+                <button disabled=working on:click=scan_faces>"Find faces"</button>
+            </Show>
+            <Show when=move || error().is_some()>
+                <span>{error().unwrap()}</span>
+            </Show>
+        </div>
+    }
+}
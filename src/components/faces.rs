@@ -0,0 +1,954 @@
+#[cfg(feature = "ssr")]
+use crate::auth;
+use crate::components::people::get_people;
+use crate::components::upload::Bbox;
+use crate::face_embedding::FaceSuggestion;
+use leptonic::components::select::OptionalSelect;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+//Clusters shown at once in the review queue, and faces shown per cluster
+#[cfg(feature = "ssr")]
+const CLUSTERS_PER_PAGE: usize = 10;
+#[cfg(feature = "ssr")]
+const FACES_SHOWN: usize = 8;
+
+/// Face found by the detector that nobody has named yet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnassignedFace {
+    pub id: i64,
+    pub bounds: Bbox,
+    pub confidence: f64,
+    /// Base64 WebP crop of the face
+    pub crop: String,
+    /// Closest named person, when face recognition is enabled
+    pub suggestion: Option<FaceSuggestion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterFace {
+    pub id: i64,
+    pub file_id: String,
+    /// Base64 WebP crop of the face
+    pub crop: String,
+}
+
+/// Unnamed faces that look like the same person.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaceCluster {
+    pub face_ids: Vec<i64>,
+    /// The first few faces of the cluster
+    pub faces: Vec<ClusterFace>,
+    pub suggestion: Option<FaceSuggestion>,
+}
+
+/// State of the last face scan of stored photos.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScanProgress {
+    pub running: bool,
+    pub total: usize,
+    pub scanned: usize,
+    /// New unassigned faces
+    pub found: usize,
+    pub failed: usize,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::ScanProgress;
+    use crate::components::upload::Bbox;
+    use crate::face_detector::{face_crop, FaceDetectors};
+    use crate::face_embedding::{cluster_faces, from_blob, to_blob, FaceGroup};
+    use leptos::ServerFnError;
+    use sqlx::SqlitePool;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Detect the faces of a stored file and keep the ones that are not
+    /// marked yet as unassigned faces.
+    pub async fn detect_faces(
+        file_id: &str,
+        image: Vec<u8>,
+        detectors: &FaceDetectors,
+        pool: &SqlitePool,
+    ) -> Result<usize, ServerFnError> {
+        //Faces rejected before are skipped as well
+        let marked: Vec<Bbox> = sqlx::query_as::<_, (u32, u32, u32, u32)>(
+            "SELECT x, y, width, height FROM userFile WHERE fileID = ? AND width > 0 AND height > 0
+            UNION ALL
+            SELECT x, y, width, height FROM faces WHERE fileID = ?",
+        )
+        .bind(file_id)
+        .bind(file_id)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|(x, y, w, h)| Bbox { x, y, w, h })
+        .collect();
+
+        let faces: Vec<_> = detectors
+            .detect(image)
+            .await?
+            .into_iter()
+            .filter(|face| !marked.iter().any(|bounds| bounds.overlaps(&face.bounds)))
+            .collect();
+
+        for face in &faces {
+            sqlx::query(
+                "INSERT INTO faces (fileID, x, y, width, height, confidence, embedding, crop)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(file_id)
+            .bind(face.bounds.x)
+            .bind(face.bounds.y)
+            .bind(face.bounds.w)
+            .bind(face.bounds.h)
+            .bind(face.confidence)
+            .bind(face.embedding.as_deref().map(to_blob))
+            .bind(&face.crop)
+            .execute(pool)
+            .await?;
+        }
+
+        Ok(faces.len())
+    }
+
+    /// Compute the missing embeddings of the faces marked on people in a
+    /// stored file, so they can be suggested for other faces.
+    pub async fn embed_marked_faces(
+        file_id: &str,
+        detectors: &FaceDetectors,
+        pool: &SqlitePool,
+    ) -> Result<usize, ServerFnError> {
+        if !detectors.embeds() {
+            return Ok(0);
+        }
+
+        let marked = sqlx::query_as::<_, (i64, u32, u32, u32, u32)>(
+            "SELECT personID, x, y, width, height FROM userFile
+            WHERE fileID = ? AND width > 0 AND height > 0 AND embedding IS NULL",
+        )
+        .bind(file_id)
+        .fetch_all(pool)
+        .await?;
+        if marked.is_empty() {
+            return Ok(0);
+        }
+
+        let path: String = sqlx::query_scalar("SELECT path FROM files WHERE id = ?")
+            .bind(file_id)
+            .fetch_one(pool)
+            .await?;
+        let image = tokio::fs::read(path).await?;
+        let boxes = marked
+            .iter()
+            .map(|&(_, x, y, w, h)| Bbox { x, y, w, h })
+            .collect();
+        let embeddings = detectors.embed(image, boxes).await?;
+
+        let mut embedded = 0;
+        for ((person_id, ..), embedding) in marked.iter().zip(embeddings) {
+            let Some(embedding) = embedding else {
+                continue;
+            };
+            sqlx::query("UPDATE userFile SET embedding = ? WHERE personID = ? AND fileID = ?")
+                .bind(to_blob(&embedding))
+                .bind(person_id)
+                .bind(file_id)
+                .execute(pool)
+                .await?;
+            embedded += 1;
+        }
+
+        Ok(embedded)
+    }
+
+    /// Background scan of stored photos for faces, one scan at a time.
+    #[derive(Debug, Default)]
+    pub struct FaceScan {
+        progress: Mutex<ScanProgress>,
+        cancelled: AtomicBool,
+    }
+
+    impl FaceScan {
+        pub fn progress(&self) -> ScanProgress {
+            self.progress.lock().unwrap().clone()
+        }
+
+        /// Start scanning the files, unless a scan is already running.
+        pub fn start(
+            self: &Arc<Self>,
+            file_ids: Vec<String>,
+            detectors: Arc<FaceDetectors>,
+            pool: SqlitePool,
+        ) -> bool {
+            {
+                let mut progress = self.progress.lock().unwrap();
+                if progress.running {
+                    return false;
+                }
+                *progress = ScanProgress {
+                    running: true,
+                    total: file_ids.len(),
+                    ..Default::default()
+                };
+            }
+            self.cancelled.store(false, Ordering::Relaxed);
+
+            let scan = self.clone();
+            tokio::spawn(async move {
+                for file_id in file_ids {
+                    if scan.cancelled.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let found = scan_file(&file_id, &detectors, &pool).await;
+
+                    let mut progress = scan.progress.lock().unwrap();
+                    progress.scanned += 1;
+                    match found {
+                        Ok(found) => progress.found += found,
+                        Err(e) => {
+                            eprintln!("Face scan of {} failed: {}", file_id, e);
+                            progress.failed += 1;
+                        }
+                    }
+                }
+                scan.progress.lock().unwrap().running = false;
+            });
+
+            true
+        }
+
+        /// Stop after the photo being scanned.
+        pub fn cancel(&self) {
+            self.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    async fn scan_file(
+        file_id: &str,
+        detectors: &FaceDetectors,
+        pool: &SqlitePool,
+    ) -> Result<usize, ServerFnError> {
+        let path: String = sqlx::query_scalar("SELECT path FROM files WHERE id = ?")
+            .bind(file_id)
+            .fetch_one(pool)
+            .await?;
+        let image = tokio::fs::read(path).await?;
+
+        let found = detect_faces(file_id, image, detectors, pool).await?;
+        embed_marked_faces(file_id, detectors, pool).await?;
+        Ok(found)
+    }
+
+    /// File of an unassigned face, if the user may see it.
+    pub async fn face_file(
+        face_id: i64,
+        user_id: i64,
+        pool: &SqlitePool,
+    ) -> Result<String, ServerFnError> {
+        use crate::image_filter::image_filter;
+
+        let file_id: Option<String> = sqlx::query_scalar("SELECT fileID FROM faces WHERE id = ?")
+            .bind(face_id)
+            .fetch_optional(pool)
+            .await?;
+
+        match file_id {
+            Some(file_id) if image_filter::can_view_file(&file_id, user_id, pool).await? => {
+                Ok(file_id)
+            }
+            _ => Err(ServerFnError::ServerError("Face not found".to_string())),
+        }
+    }
+
+    /// Clusters of all unnamed faces with an embedding. Naming or rejecting
+    /// faces only removes them from their cluster; the faces are clustered
+    /// again when new ones are found.
+    #[derive(Debug, Default)]
+    pub struct ClusterCache {
+        clusters: tokio::sync::Mutex<Option<(HashSet<i64>, Vec<FaceGroup>)>>,
+    }
+
+    impl ClusterCache {
+        pub async fn groups(
+            &self,
+            threshold: f32,
+            pool: &SqlitePool,
+        ) -> Result<Vec<FaceGroup>, ServerFnError> {
+            let mut clusters = self.clusters.lock().await;
+            let ids: HashSet<i64> = sqlx::query_scalar(
+                "SELECT id FROM faces WHERE embedding IS NOT NULL AND dismissed = 0",
+            )
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+
+            if let Some((clustered, groups)) = clusters.as_mut() {
+                if ids.is_subset(clustered) {
+                    for group in groups.iter_mut() {
+                        group.face_ids.retain(|id| ids.contains(id));
+                    }
+                    groups.retain(|group| !group.face_ids.is_empty());
+                    *clustered = ids;
+                    return Ok(groups.clone());
+                }
+            }
+
+            let faces: Vec<(i64, Vec<f32>)> = sqlx::query_as::<_, (i64, Vec<u8>)>(
+                "SELECT id, embedding FROM faces WHERE embedding IS NOT NULL AND dismissed = 0
+                ORDER BY id",
+            )
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .filter_map(|(id, blob)| Some((id, from_blob(&blob)?)))
+            .collect();
+
+            //Clustering is slow for large libraries
+            let groups =
+                tokio::task::spawn_blocking(move || cluster_faces(&faces, threshold)).await?;
+            *clusters = Some((ids, groups.clone()));
+            Ok(groups)
+        }
+    }
+
+    /// File and stored crop of each face. Faces found before crops were
+    /// stored are cropped from their photo once.
+    pub async fn face_crops(
+        face_ids: &[i64],
+        pool: &SqlitePool,
+    ) -> Result<HashMap<i64, (String, Vec<u8>)>, ServerFnError> {
+        let mut crops = HashMap::new();
+        let mut uncropped: HashMap<String, Vec<(i64, String, Bbox)>> = HashMap::new();
+        for face_id in face_ids {
+            let (file_id, path, x, y, w, h, crop) =
+                sqlx::query_as::<_, (String, String, u32, u32, u32, u32, Option<Vec<u8>>)>(
+                    "SELECT fc.fileID, f.path, fc.x, fc.y, fc.width, fc.height, fc.crop
+                FROM faces fc INNER JOIN files f ON f.id = fc.fileID WHERE fc.id = ?",
+                )
+                .bind(face_id)
+                .fetch_one(pool)
+                .await?;
+            match crop {
+                Some(crop) => {
+                    crops.insert(*face_id, (file_id, crop));
+                }
+                None => uncropped.entry(path).or_default().push((
+                    *face_id,
+                    file_id,
+                    Bbox { x, y, w, h },
+                )),
+            }
+        }
+
+        for (path, faces) in uncropped {
+            let boxes: Vec<Bbox> = faces.iter().map(|face| face.2.clone()).collect();
+            let cropped = tokio::task::spawn_blocking(move || {
+                let image = image::open(path).ok()?;
+                Some(
+                    boxes
+                        .iter()
+                        .map(|bounds| face_crop(&image, bounds))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .await?;
+
+            for ((face_id, file_id, _), crop) in faces.into_iter().zip(cropped.unwrap_or_default())
+            {
+                let Some(crop) = crop else {
+                    continue;
+                };
+                sqlx::query("UPDATE faces SET crop = ? WHERE id = ?")
+                    .bind(&crop)
+                    .bind(face_id)
+                    .execute(pool)
+                    .await?;
+                crops.insert(face_id, (file_id, crop));
+            }
+        }
+
+        Ok(crops)
+    }
+
+    /// Embeddings of all named faces with the id and name of the person.
+    pub async fn known_faces(
+        pool: &SqlitePool,
+    ) -> Result<Vec<(i64, String, Vec<f32>)>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (i64, String, Vec<u8>)>(
+            "SELECT uf.personID, p.name, uf.embedding FROM userFile uf
+            INNER JOIN people p ON p.id = uf.personID
+            WHERE uf.embedding IS NOT NULL",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, name, blob)| Some((id, name, from_blob(&blob)?)))
+            .collect())
+    }
+}
+
+#[server(UnassignedFaces, "/api")]
+pub async fn get_unassigned_faces(file_id: String) -> Result<Vec<UnassignedFace>, ServerFnError> {
+    use crate::face_embedding::{from_blob, nearest_person, DEFAULT_MATCH_THRESHOLD};
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    if !image_filter::can_view_file(&file_id, user.id, &pool).await? {
+        return Err(ServerFnError::ServerError(
+            "You are not authorized to view this image".to_string(),
+        ));
+    }
+
+    let faces = sqlx::query_as::<_, (i64, u32, u32, u32, u32, f64, Option<Vec<u8>>)>(
+        "SELECT id, x, y, width, height, confidence, embedding FROM faces
+        WHERE fileID = ? AND dismissed = 0 ORDER BY x, y",
+    )
+    .bind(file_id)
+    .fetch_all(&pool)
+    .await?;
+
+    //Suggest people only when some face has an embedding
+    let known = if faces.iter().any(|face| face.6.is_some()) {
+        ssr::known_faces(&pool).await?
+    } else {
+        Vec::new()
+    };
+    let threshold = use_context::<std::sync::Arc<crate::face_detector::FaceDetectors>>()
+        .map_or(DEFAULT_MATCH_THRESHOLD, |detectors| {
+            detectors.config().match_threshold
+        });
+    let ids: Vec<i64> = faces.iter().map(|face| face.0).collect();
+    let mut crops = ssr::face_crops(&ids, &pool).await?;
+
+    Ok(faces
+        .into_iter()
+        .map(|(id, x, y, w, h, confidence, embedding)| UnassignedFace {
+            id,
+            bounds: Bbox { x, y, w, h },
+            confidence,
+            crop: crops
+                .remove(&id)
+                .map(|(_, crop)| base64::encode(crop))
+                .unwrap_or_default(),
+            suggestion: embedding
+                .and_then(|blob| from_blob(&blob))
+                .and_then(|embedding| nearest_person(&embedding, &known, threshold)),
+        })
+        .collect())
+}
+
+//Tag a person on the photos of the faces, with the box of each face.
+//Faces on photos where the person already has a box stay unnamed, and
+//their number is returned.
+#[server(NameFaces, "/api")]
+pub async fn name_faces(face_ids: Vec<i64>, name: String) -> Result<usize, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ServerFnError::ServerError("A name is required".to_string()));
+    }
+
+    for face_id in &face_ids {
+        ssr::face_file(*face_id, user.id, &pool).await?;
+    }
+
+    let person = crate::components::people::ssr::person_id(&name, &pool).await?;
+
+    //A person tagged without a box gets the box of the face
+    let mut kept = 0;
+    let mut tx = pool.begin().await?;
+    for face_id in face_ids {
+        let named = sqlx::query(
+            "INSERT INTO userFile (personID, fileID, x, y, width, height, embedding)
+            SELECT ?, fileID, x, y, width, height, embedding FROM faces WHERE id = ?
+            ON CONFLICT (personID, fileID) DO UPDATE SET
+                x = excluded.x, y = excluded.y, width = excluded.width,
+                height = excluded.height, embedding = excluded.embedding
+            WHERE COALESCE(userFile.width, 0) = 0 OR COALESCE(userFile.height, 0) = 0",
+        )
+        .bind(person)
+        .bind(face_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if named == 0 {
+            kept += 1;
+            continue;
+        }
+        sqlx::query("DELETE FROM faces WHERE id = ?")
+            .bind(face_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(kept)
+}
+
+//Hide boxes that are not faces. They are kept so scanning the photo again
+//does not find them as new faces.
+#[server(DismissFaces, "/api")]
+pub async fn dismiss_faces(face_ids: Vec<i64>) -> Result<(), ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    for face_id in &face_ids {
+        ssr::face_file(*face_id, user.id, &pool).await?;
+    }
+
+    let mut tx = pool.begin().await?;
+    for face_id in face_ids {
+        sqlx::query("UPDATE faces SET dismissed = 1, crop = NULL WHERE id = ?")
+            .bind(face_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+//Look for new faces in the given photos, or in all photos when none are given
+#[server(StartFaceScan, "/api")]
+pub async fn start_face_scan(
+    #[server(default)] file_ids: Vec<String>,
+) -> Result<usize, ServerFnError> {
+    use crate::face_detector::FaceDetectors;
+    use std::sync::Arc;
+    auth::authorized("admin").await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let (Some(scan), Some(detectors)) = (
+        use_context::<Arc<ssr::FaceScan>>(),
+        use_context::<Arc<FaceDetectors>>(),
+    ) else {
+        return Err(ServerFnError::ServerError(
+            "Face detection is not available".to_string(),
+        ));
+    };
+
+    let file_ids = if file_ids.is_empty() {
+        sqlx::query_scalar("SELECT id FROM files ORDER BY uploadDate")
+            .fetch_all(&pool)
+            .await?
+    } else {
+        file_ids
+    };
+    let count = file_ids.len();
+
+    if !scan.start(file_ids, detectors, pool) {
+        return Err(ServerFnError::ServerError(
+            "A face scan is already running".to_string(),
+        ));
+    }
+
+    Ok(count)
+}
+
+#[server(FaceScanProgress, "/api")]
+pub async fn get_face_scan_progress() -> Result<ScanProgress, ServerFnError> {
+    auth::authorized("admin").await?;
+
+    Ok(use_context::<std::sync::Arc<ssr::FaceScan>>()
+        .map(|scan| scan.progress())
+        .unwrap_or_default())
+}
+
+#[server(CancelFaceScan, "/api")]
+pub async fn cancel_face_scan() -> Result<(), ServerFnError> {
+    auth::authorized("admin").await?;
+
+    if let Some(scan) = use_context::<std::sync::Arc<ssr::FaceScan>>() {
+        scan.cancel();
+    }
+
+    Ok(())
+}
+
+//Unnamed faces the user can see, grouped by similarity, largest group first.
+//Faces without an embedding are reviewed one at a time.
+#[server(FaceClusters, "/api")]
+pub async fn get_face_clusters(
+    #[server(default)] skip: Vec<i64>,
+) -> Result<Vec<FaceCluster>, ServerFnError> {
+    use crate::face_embedding::{nearest_person, FaceGroup, DEFAULT_MATCH_THRESHOLD};
+    use crate::image_filter::image_filter;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let (access, access_binds) = image_filter::access_condition(user.id);
+    let query = format!(
+        "SELECT fc.id FROM faces fc INNER JOIN files f ON f.id = fc.fileID
+        WHERE fc.dismissed = 0 AND {} ORDER BY fc.id",
+        access
+    );
+    let mut query = sqlx::query_scalar::<_, i64>(&query);
+    for bind in access_binds {
+        query = query.bind(bind);
+    }
+    let skip: HashSet<i64> = skip.into_iter().collect();
+    let faces: Vec<i64> = query
+        .fetch_all(&pool)
+        .await?
+        .into_iter()
+        .filter(|id| !skip.contains(id))
+        .collect();
+    let visible: HashSet<i64> = faces.iter().copied().collect();
+
+    let threshold = use_context::<Arc<crate::face_detector::FaceDetectors>>()
+        .map_or(DEFAULT_MATCH_THRESHOLD, |detectors| {
+            detectors.config().match_threshold
+        });
+    let groups = match use_context::<Arc<ssr::ClusterCache>>() {
+        Some(cache) => cache.groups(threshold, &pool).await?,
+        None => Vec::new(),
+    };
+
+    let mut groups: Vec<FaceGroup> = groups
+        .into_iter()
+        .filter_map(|mut group| {
+            group.face_ids.retain(|id| visible.contains(id));
+            (!group.face_ids.is_empty()).then_some(group)
+        })
+        .collect();
+    groups.sort_by_key(|group| std::cmp::Reverse(group.face_ids.len()));
+    let clustered: HashSet<i64> = groups
+        .iter()
+        .flat_map(|group| group.face_ids.iter().copied())
+        .collect();
+    groups.extend(
+        faces
+            .iter()
+            .filter(|id| !clustered.contains(id))
+            .map(|id| FaceGroup {
+                face_ids: vec![*id],
+                centroid: Vec::new(),
+            }),
+    );
+    groups.truncate(CLUSTERS_PER_PAGE);
+
+    let shown: Vec<i64> = groups
+        .iter()
+        .flat_map(|group| group.face_ids.iter().take(FACES_SHOWN).copied())
+        .collect();
+    let mut crops = ssr::face_crops(&shown, &pool).await?;
+    let known = ssr::known_faces(&pool).await?;
+
+    Ok(groups
+        .into_iter()
+        .map(|group| FaceCluster {
+            faces: group
+                .face_ids
+                .iter()
+                .take(FACES_SHOWN)
+                .filter_map(|id| {
+                    let (file_id, crop) = crops.remove(id)?;
+                    Some(ClusterFace {
+                        id: *id,
+                        file_id,
+                        crop: base64::encode(crop),
+                    })
+                })
+                .collect(),
+            suggestion: nearest_person(&group.centroid, &known, threshold),
+            face_ids: group.face_ids,
+        })
+        .collect())
+}
+
+//Crops of the unnamed faces in a photo, each with a name picker
+#[component]
+pub fn UnnamedFaces(
+    #[prop(into)] file_id: String,
+    #[prop(into)] on_named: Callback<()>,
+) -> impl IntoView {
+    let faces = create_resource(
+        move || file_id.clone(),
+        |file_id| async move { get_unassigned_faces(file_id).await.unwrap_or_default() },
+    );
+
+    let users = create_rw_signal(vec![]);
+    spawn_local(async move {
+        if let Ok(p) = get_people().await {
+            users.set(p.into_iter().map(|person| person.name).collect());
+        }
+    });
+
+    view! {
+        <Transition fallback=|| ()>
+            {move || faces.get().filter(|faces| !faces.is_empty()).map(|list| {
+                view! {
+                    <div class="unnamed-faces">
+                        <h4>"Who is this?"</h4>
+                        <div class="faces">
+                            {list.into_iter().map(|face| {
+                                let face_id = face.id;
+                                let (name, set_name) = create_signal(None::<String>);
+                                let save = move |selected: Option<String>| {
+                                    let Some(selected) = selected else {
+                                        return;
+                                    };
+                                    set_name(Some(selected.clone()));
+                                    spawn_local(async move {
+                                        match name_faces(vec![face_id], selected.clone()).await {
+                                            Ok(0) => {
+                                                faces.refetch();
+                                                on_named(());
+                                            }
+                                            Ok(_) => {
+                                                set_name(None);
+                                                logging::log!("{} already has a face marked in this photo", selected);
+                                            }
+                                            Err(e) => logging::log!("{}", e),
+                                        }
+                                    });
+                                };
+                                view! {
+                                    <div class="face">
+                                        <img src=format!("data:image/webp;base64,{}", face.crop)/>
+                                        <OptionalSelect class="person"
+                                            options=users
+                                            search_text_provider=move |o: String| o
+                                            render_option=move |o: String| o
+                                            selected=name
+                                            add=move |v: String| {
+                                                users.update(|users| users.push(v.clone()));
+                                                save(Some(v));
+                                            }
+                                            set_selected=save
+                                            allow_deselect=false
+                                        />
+                                        {face.suggestion.map(|suggestion| {
+                                            let label = format!("{}?", suggestion.name);
+                                            view! {
+                                                <button class="suggestion" on:click=move |_| save(Some(suggestion.name.clone()))>
+                                                    {label}
+                                                </button>
+                                            }
+                                        })}
+                                        <button on:click=move |_| spawn_local(async move {
+                                            if dismiss_faces(vec![face_id]).await.is_ok() {
+                                                faces.refetch();
+                                            }
+                                        })>"Not a face"</button>
+                                    </div>
+                                }
+                            }).collect_view()}
+                        </div>
+                    </div>
+                }
+            })}
+        </Transition>
+    }
+}
+
+//One cluster in the review queue
+#[component]
+fn ClusterReview(
+    cluster: FaceCluster,
+    users: RwSignal<Vec<String>>,
+    #[prop(into)] on_done: Callback<()>,
+    #[prop(into)] on_skip: Callback<Vec<i64>>,
+) -> impl IntoView {
+    let face_ids = store_value(cluster.face_ids);
+    let (name, set_name) = create_signal(None::<String>);
+    let (error, set_error) = create_signal(None::<String>);
+
+    let done = move |result: Result<(), ServerFnError>| match result {
+        Ok(_) => on_done(()),
+        Err(e) => set_error(Some(e.to_string())),
+    };
+    let save = move |selected: Option<String>| {
+        let Some(selected) = selected else {
+            return;
+        };
+        set_name(Some(selected.clone()));
+        spawn_local(async move {
+            match name_faces(face_ids.get_value(), selected.clone()).await {
+                //The faces that were left show up again after the refetch
+                Ok(kept) if kept > 0 => {
+                    logging::log!(
+                        "{} faces left unnamed, {} already has a face in their photos",
+                        kept,
+                        selected
+                    );
+                    on_done(())
+                }
+                result => done(result.map(|_| ())),
+            }
+        });
+    };
+    let reject = move |ids: Vec<i64>| spawn_local(async move { done(dismiss_faces(ids).await) });
+
+    let count = face_ids.with_value(|ids| ids.len());
+    let hidden = count - cluster.faces.len();
+
+    view! {
+        <div class="face-cluster">
+            <div class="faces">
+                {cluster.faces.into_iter().map(|face| {
+                    let id = face.id;
+                    view! {
+                        <div class="face">
+                            <img src=format!("data:image/webp;base64,{}", face.crop)/>
+                            <button class="reject" title="Not a face" on:click=move |_| reject(vec![id])>"✕"</button>
+                        </div>
+                    }
+                }).collect_view()}
+                <Show when=move || { hidden > 0 }>
+                    <span class="more">{format!("+{}", hidden)}</span>
+                </Show>
+            </div>
+            <div class="cluster-actions">
+                <OptionalSelect class="person"
+                    options=users
+                    search_text_provider=move |o: String| o
+                    render_option=move |o: String| o
+                    selected=name
+                    add=move |v: String| {
+                        users.update(|users| users.push(v.clone()));
+                        save(Some(v));
+                    }
+                    set_selected=save
+                    allow_deselect=false
+                />
+                {cluster.suggestion.map(|suggestion| {
+                    let label = format!("{}?", suggestion.name);
+                    view! {
+                        <button class="suggestion" on:click=move |_| save(Some(suggestion.name.clone()))>
+                            {label}
+                        </button>
+                    }
+                })}
+                <button on:click=move |_| reject(face_ids.get_value())>
+                    {if count == 1 { "Not a face" } else { "Not faces" }}
+                </button>
+                <button on:click=move |_| on_skip(face_ids.get_value())>"Skip"</button>
+            </div>
+            <Show when=move || error().is_some()>
+                <p>{move || error().unwrap_or_default()}</p>
+            </Show>
+        </div>
+    }
+}
+
+//Review queue for naming detected faces a cluster at a time
+#[component]
+pub fn FaceReviewPage() -> impl IntoView {
+    //Skipped faces are left out until the page is loaded again
+    let skipped = create_rw_signal(Vec::<i64>::new());
+    let clusters = create_resource(
+        move || skipped.get(),
+        |skip| async move { get_face_clusters(skip).await },
+    );
+
+    let users = create_rw_signal(vec![]);
+    spawn_local(async move {
+        if let Ok(p) = get_people().await {
+            users.set(p.into_iter().map(|person| person.name).collect());
+        }
+    });
+
+    view! {
+        <h1>"Unnamed faces"</h1>
+        <Transition fallback=move || view! {<p>"Finding faces"</p>}>
+            <ErrorBoundary fallback=|_| {view! {<p>"Something went wrong"</p>}}>
+                {move || clusters.get().map(|res| res.map(|list| {
+                    if list.is_empty() {
+                        return view! {<p>"All faces are named"</p>}.into_view();
+                    }
+                    list.into_iter().map(|cluster| view! {
+                        <ClusterReview
+                            cluster=cluster
+                            users=users
+                            on_done=move |_| clusters.refetch()
+                            on_skip=move |ids: Vec<i64>| skipped.update(|skipped| skipped.extend(ids))/>
+                    }).collect_view()
+                }))}
+            </ErrorBoundary>
+        </Transition>
+    }
+}
+
+//Admin controls for scanning the library for faces again
+#[component]
+pub fn FaceScanPanel() -> impl IntoView {
+    let progress = create_resource(|| (), |_| async { get_face_scan_progress().await });
+    let (message, set_message) = create_signal(None::<String>);
+
+    //Follow a running scan
+    let _ = leptos_use::use_interval_fn(
+        move || {
+            if progress
+                .get_untracked()
+                .and_then(|p| p.ok())
+                .is_some_and(|p| p.running)
+            {
+                progress.refetch();
+            }
+        },
+        2000_u64,
+    );
+
+    let start = move |_| {
+        spawn_local(async move {
+            match start_face_scan(vec![]).await {
+                Ok(_) => set_message(None),
+                Err(e) => set_message(Some(e.to_string())),
+            }
+            progress.refetch();
+        })
+    };
+    let cancel = move |_| {
+        spawn_local(async move {
+            if let Err(e) = cancel_face_scan().await {
+                set_message(Some(e.to_string()));
+            }
+            progress.refetch();
+        })
+    };
+
+    view! {
+        <h2>"Face scan"</h2>
+        <p>"Looks for faces that are not marked yet in stored photos."</p>
+        <Transition fallback=|| ()>
+            {move || progress.get().and_then(|p| p.ok()).map(|p| {
+                let running = p.running;
+                view! {
+                    {(p.total > 0).then(|| view! {
+                        <p>
+                            {format!("{} of {} photos scanned, {} new faces", p.scanned, p.total, p.found)}
+                            {(p.failed > 0).then(|| format!(", {} failed", p.failed))}
+                        </p>
+                    })}
+                    <Show
+                        when=move || running
+                        fallback=move || view! {
+                            <button on:click=start style="width: 200px;">"Scan all photos"</button>
+                        }>
+                        <button on:click=cancel style="width: 200px;">"Stop"</button>
+                    </Show>
+                }
+            })}
+        </Transition>
+        <Show when=move || message().is_some()>
+            <p>{message().unwrap()}</p>
+        </Show>
+    }
+}
@@ -7,6 +7,7 @@ use leptos::*;
 use leptos_use::{use_infinite_scroll_with_options, UseInfiniteScrollOptions};
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::HashSet;
 #[cfg(feature = "ssr")]
 use std::fs::File;
 #[cfg(feature = "ssr")]
@@ -18,13 +19,35 @@ use std::io::Read;
 pub struct ImageDb {
     id: String,
     path: String,
-    upload_date: String,
+    date: Option<String>,
 }
 
-#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+//Position after the last image of a page. Opaque to the client, which only sends it back.
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, Hash, PartialEq)]
-pub struct PrevImageDb {
-    uploaded_date: String,
+pub struct FeedCursor {
+    keys: Vec<String>,
+    id: String,
+    /// Date of the last image, so month headers carry on across pages
+    date: Option<String>,
+}
+
+//A page of the feed, `next` is `None` on the last page
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FeedPage {
+    pub elements: Vec<Element>,
+    pub next: Option<FeedCursor>,
+}
+
+//Year and month headers of a date. Photos without a capture date are grouped as undated.
+#[cfg(feature = "ssr")]
+fn year_month(date: &Option<String>) -> (String, String) {
+    match date
+        .as_deref()
+        .and_then(|d| Some((d.get(0..4)?, d.get(5..7)?)))
+    {
+        Some((year, month)) => (year.to_string(), month.to_string()),
+        None => ("Undated".to_string(), String::new()),
+    }
 }
 
 //Takes a date string and image struct
@@ -34,48 +57,99 @@ pub enum Element {
     ImageDb(RwSignal<ImageDb>),
 }
 
-//Fetch images from database
+//Fetch a page of images from database, starting after `cursor`
 #[server(Feed, "/api")]
 pub async fn fetch_files(
-    db_index: usize,
+    #[server(default)] cursor: Option<FeedCursor>,
     count: usize,
-    tags: Option<(String, Vec<String>)>,
-    people: Option<(String, Vec<i64>)>,
-) -> Result<Vec<Element>, ServerFnError> {
+    #[server(default)] filter: Filters,
+) -> Result<FeedPage, ServerFnError> {
     use crate::image_filter::image_filter;
-    auth::logged_in().await?;
+    use sqlx::{FromRow, Row};
+    let user = auth::logged_in().await?;
 
     //DB connection
     use crate::app::ssr::*;
     let pool = pool()?;
 
-    // Return nothing if index above limit
-    let total_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files")
-        .fetch_one(&pool)
-        .await?;
-    if db_index as i64 > total_count {
-        return Ok(vec![]);
+    let sort = image_filter::feed_order(&filter);
+    let mut base_query = format!(
+        "SELECT DISTINCT f.id, f.path, {} AS date",
+        sort.date_column.unwrap_or("f.uploadDate")
+    );
+    for (i, key) in sort.keys.iter().enumerate() {
+        base_query.push_str(&format!(", {} AS k{}", key.expr, i));
+    }
+    base_query.push_str(" FROM files f");
+
+    let mut binds = sort.binds.clone();
+    let (mut conditions, joins, filter_binds) =
+        image_filter::prepare_filtered_query(&filter).await?;
+    binds.extend(filter_binds);
+
+    // Hide files from private albums the user has no access to
+    let (access, access_binds) = image_filter::access_condition(user.id);
+    conditions.push(access);
+    binds.extend(access_binds);
+
+    if let Some(cursor) = &cursor {
+        if cursor.keys.len() != sort.keys.len() {
+            return Err(ServerFnError::ServerError(
+                "The feed cursor does not match the sort order".to_string(),
+            ));
+        }
+        conditions.push(sort.after());
+        binds.extend(sort.binds.iter().cloned());
+        binds.extend(cursor.keys.iter().cloned());
+        binds.push(cursor.id.clone());
     }
 
-    let base_query = "SELECT DISTINCT f.id, f.path, f.uploadDate AS upload_date FROM files f";
-    let (conditions, joins, binds) =
-        image_filter::prepare_filtered_query_with_pagination(tags, people, count, db_index).await;
+    // Result columns can be sorted on by name
+    let order_by = (0..sort.keys.len())
+        .map(|i| format!("k{} DESC, ", i))
+        .collect::<String>()
+        + "f.id DESC";
 
     let query = image_filter::build_filtered_query(
-        base_query.to_string(),
+        base_query,
         conditions,
         joins,
-        Some("f.uploadDate DESC".to_string()),
+        Some(order_by),
         Some(count),
-        Some(db_index),
+        None,
     );
 
-    let mut query = sqlx::query_as(&query);
+    let mut query = sqlx::query(&query);
     for bind in binds {
         query = query.bind(bind);
     }
+    let rows = query.fetch_all(&pool).await?;
+
+    let mut files: Vec<ImageDb> = Vec::with_capacity(rows.len());
+    let mut next = None;
+    for row in &rows {
+        let image = ImageDb::from_row(row)?;
+        let mut keys = Vec::with_capacity(sort.keys.len());
+        for (i, key) in sort.keys.iter().enumerate() {
+            let column = format!("k{}", i);
+            keys.push(if key.numeric {
+                row.try_get::<f64, _>(column.as_str())?.to_string()
+            } else {
+                row.try_get::<String, _>(column.as_str())?
+            });
+        }
+        next = Some(FeedCursor {
+            keys,
+            id: image.id.clone(),
+            date: image.date.clone(),
+        });
+        files.push(image);
+    }
+    // A short page is the last one
+    if files.len() < count {
+        next = None;
+    }
 
-    let mut files: Vec<ImageDb> = query.fetch_all(&pool).await?;
     for img in &mut files {
         // Read the image file
         let mut file = File::open(&img.path).expect("Failed to open image file");
@@ -92,69 +166,72 @@ pub async fn fetch_files(
 
     let mut grouped_images: Vec<Element> = Vec::new();
 
-    //Check previous date to prevent duplicate dates
-    let mut prevfile: Option<PrevImageDb> = None;
-    //Prevent checking previous date on the first request
-    if db_index > 0 {
-        prevfile = Some(sqlx::query_as::<_, PrevImageDb>(
-            "SELECT uploadDate AS uploaded_date FROM files ORDER BY uploadDate DESC LIMIT ? OFFSET ?;",
-        )
-        .bind(1.to_string())
-        .bind((db_index-1).to_string())
-        .fetch_one(&pool)
-        .await?);
+    //Manually ordered albums and search results are not grouped by date
+    if sort.date_column.is_none() {
+        grouped_images.extend(
+            files
+                .into_iter()
+                .map(|image| Element::ImageDb(create_rw_signal(image))),
+        );
+        return Ok(FeedPage {
+            elements: grouped_images,
+            next,
+        });
     }
 
     let mut current_month = String::new();
     let mut current_year = String::new();
 
-    //When there is a previous date
-    if prevfile.is_some() {
-        //Access previous date requested
-        current_month = prevfile.clone().unwrap().uploaded_date[5..7].to_string();
-        current_year = prevfile.clone().unwrap().uploaded_date[0..4].to_string();
+    //Continue the headers of the previous page
+    if let Some(cursor) = &cursor {
+        (current_year, current_month) = year_month(&cursor.date);
     }
 
-    let mut c: i64 = 0;
     //Iterates over sorted images and adds years and months
     for image in files {
-        let year = image.upload_date[0..4].to_string();
-        let month = image.upload_date[5..7].to_string();
+        let (year, month) = year_month(&image.date);
         if month != current_month || year != current_year {
             //Add year on change
             if year != current_year {
                 grouped_images.push(Element::String(create_rw_signal(year.to_string())));
                 current_year = year.to_string();
             }
-            //Add month on change
-            grouped_images.push(Element::String(create_rw_signal(month.to_string())));
+            //Add month on change, undated photos have none
+            if !month.is_empty() {
+                grouped_images.push(Element::String(create_rw_signal(month.to_string())));
+            }
             current_month = month.to_string();
         }
-        c = c + 1;
         grouped_images.push(Element::ImageDb(create_rw_signal(image)));
     }
 
-    Ok(grouped_images)
+    Ok(FeedPage {
+        elements: grouped_images,
+        next,
+    })
 }
 
 //Images per infinite feed requst
 const FETCH_IMAGE_COUNT: usize = 10;
 
 async fn request_wrapper(
-    db_index: usize,
+    cursor: StoredValue<Option<FeedCursor>>,
     count: usize,
     ready_lock: WriteSignal<bool>,
-    tags: Option<(String, Vec<String>)>,
-    people: Option<(String, Vec<i64>)>,
+    filter: Filters,
 ) -> Vec<Element> {
     ready_lock(false);
-    let result = fetch_files(db_index, count, tags, people).await.unwrap();
+    let page = fetch_files(cursor.get_value(), count, filter)
+        .await
+        .unwrap();
 
-    if !result.is_empty() {
+    //Only ask for more when there is a next page
+    if page.next.is_some() {
         ready_lock(true);
     }
+    cursor.set_value(page.next);
 
-    result
+    page.elements
 }
 
 //Creates an infinite feed of images
@@ -163,6 +240,9 @@ pub fn infinite_feed<F>(
     on_image_click: F,
     send_id: ReadSignal<Option<String>>,
     filter: ReadSignal<Filters>,
+    //When selecting, clicking an image toggles it in `selected` instead of opening it
+    selecting: ReadSignal<bool>,
+    selected: RwSignal<HashSet<String>>,
 ) -> impl IntoView
 where
     F: Fn(String) + 'static + Clone + Copy,
@@ -172,27 +252,25 @@ where
     let (ready, set_ready) = create_signal(true);
     let (loading, set_loading) = create_signal(true);
 
-    let (db_index, set_db_index) = create_signal(0);
+    //Number of pages requested by scrolling, and where the next one starts
+    let (page, set_page) = create_signal(0);
+    let cursor = store_value(None::<FeedCursor>);
 
     let initImgs: Vec<Element> = vec![];
     let (images, set_images) = create_signal(initImgs);
 
     // Signal with resource called every time the bottom is reached in infinite feed
     let _image_updater = create_resource(
-        move || (db_index.get()),
+        move || (page.get()),
         move |_| async move {
-            if db_index.get_untracked() == 0 {
+            if page.get_untracked() == 0 {
                 return;
             }
-            let l_tags = filter.get_untracked().tags.clone();
-            let l_people = filter.get_untracked().people.clone();
-
             let images = request_wrapper(
-                db_index.get_untracked() as usize,
+                cursor,
                 FETCH_IMAGE_COUNT as usize,
                 set_ready,
-                l_tags,
-                l_people,
+                filter.get_untracked(),
             )
             .await;
             set_images.update(|imgs| imgs.extend(images));
@@ -204,12 +282,11 @@ where
         move || (filter.get()),
         move |_| async move {
             set_images.set(vec![]);
-            set_db_index.set(0);
-
-            let l_tags = filter.get_untracked().tags.clone();
-            let l_people = filter.get_untracked().people.clone();
+            set_page.set(0);
+            cursor.set_value(None);
 
-            let images = request_wrapper(0, FETCH_IMAGE_COUNT, set_ready, l_tags, l_people).await;
+            let images =
+                request_wrapper(cursor, FETCH_IMAGE_COUNT, set_ready, filter.get_untracked()).await;
             set_images.update(|imgs| imgs.extend(images));
             set_loading(false);
         },
@@ -247,14 +324,12 @@ where
             }
             set_loading(true);
 
-            //Index counter for DB
-            let newIndex = db_index.get_untracked() + FETCH_IMAGE_COUNT;
             logging::log!(
-                "Requesting {} more images. Index: {}",
+                "Requesting {} more images. Page: {}",
                 FETCH_IMAGE_COUNT,
-                db_index.get_untracked() + FETCH_IMAGE_COUNT
+                page.get_untracked() + 1
             );
-            set_db_index.set(newIndex);
+            set_page.update(|page| *page += 1);
         },
         UseInfiniteScrollOptions::default().distance(300.0),
     );
@@ -283,12 +358,23 @@ where
                     //Image
                         Element::ImageDb(ref img) => {
                         view!{
-                            <div class={move || imageDisplayClass.get()}>
+                            <div class={move || imageDisplayClass.get()}
+                                class:selected={
+                                    let id = img.get().id.clone();
+                                    move || selecting.get() && selected.with(|s| s.contains(&id))
+                                }>
                             {
                                 let id = img.get().id.clone();
                                 view!{
                                     <img
-                                        on:click=move |_|{on_image_click(id.clone())}
+                                        on:click=move |_|{
+                                            if selecting.get_untracked() {
+                                                let id = id.clone();
+                                                selected.update(|s| if !s.remove(&id) {s.insert(id);});
+                                            } else {
+                                                on_image_click(id.clone())
+                                            }
+                                        }
                                         src={format!("data:image/jpeg;base64,{}", img.get().path)} alt="Base64 Image" class="image imageSmooth" />
                                 }
                             }
@@ -299,7 +385,7 @@ where
                     let date_clone = date.clone(); //Allow str to reach all the way in
                     view!{
                     <div class={move || feedDisplayClass.get()}>{
-                                    match date_clone.get().parse().unwrap() {
+                                    match date_clone.get().parse().unwrap_or(0) {
                                         1 => "January".to_string(),
                                         2 => "February".to_string(),
                                         3 => "March".to_string(),
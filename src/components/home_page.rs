@@ -1,7 +1,13 @@
+use crate::components::albums::{get_albums, AlbumArranger, AlbumManager};
+use crate::components::bulk::BulkActions;
 use crate::components::dialog::Dialog;
 use crate::components::feed::InfiniteFeed;
 use crate::components::image_view::ImageView;
+use crate::components::map::{parse_point, GeoFilter, PhotoMap};
+use crate::components::memories::OnThisDay;
+use crate::components::saved_filters::SavedFilterList;
 use crate::components::upload::UploadMedia;
+use crate::gazetteer::PlaceName;
 use leptos::*;
 #[cfg(feature = "ssr")]
 use std::fs;
@@ -10,13 +16,58 @@ use std::fs;
 use crate::auth;
 use leptonic::components::icon::Icon;
 use leptonic::components::select::Multiselect;
-use leptos::html::Select;
+use leptos::html::{Input, Select};
+use leptos_router::use_query_map;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Filters {
     pub tags: Option<(String, Vec<String>)>,
     pub people: Option<(String, Vec<i64>)>,
+    /// HAS or NOT one of these uploaders
+    pub uploader: Option<(String, Vec<i64>)>,
+    /// HAS or NOT taken in one of these places
+    pub places: Option<(String, Vec<PlaceName>)>,
+    pub album: Option<String>,
+    pub order: FeedOrder,
+    /// Capture date range, YYYY-MM-DD
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Free text search in captions, tags, people and locations
+    pub search: Option<String>,
+    /// Filter expression, see `filter_query::parse`
+    pub query: Option<String>,
+    /// Where the photos were taken
+    pub geo: Option<GeoFilter>,
+}
+
+//How the feed is sorted. Manual order only applies when browsing an album.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeedOrder {
+    #[default]
+    UploadDate,
+    CaptureDate,
+    Manual,
+}
+
+impl FeedOrder {
+    pub fn value(&self) -> &'static str {
+        match self {
+            FeedOrder::UploadDate => "upload",
+            FeedOrder::CaptureDate => "capture",
+            FeedOrder::Manual => "manual",
+        }
+    }
+
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "capture" => FeedOrder::CaptureDate,
+            "manual" => FeedOrder::Manual,
+            _ => FeedOrder::UploadDate,
+        }
+    }
 }
 
 #[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
@@ -31,9 +82,10 @@ pub async fn get_tags() -> Result<Vec<Tag>, ServerFnError> {
     use crate::db::ssr::pool;
     let pool = pool()?;
 
-    let tags = sqlx::query_as::<_, Tag>("SELECT tagString as tag_string FROM tags")
+    let mut tags = sqlx::query_as::<_, Tag>("SELECT tagString as tag_string FROM tags")
         .fetch_all(&pool)
         .await?;
+    crate::components::tags::sort_tree(&mut tags);
 
     Ok(tags)
 }
@@ -45,37 +97,64 @@ pub struct ImageDb {
     path: String,
 }
 
+//Neighbour of an image in the feed for the filter, `offset` images further or back
 #[server(NextImageId, "/api")]
 pub async fn next_prev_image_id(
     prev_id: String,
     offset: i16,
+    #[server(default)] filter: Filters,
 ) -> Result<Option<String>, ServerFnError> {
     use crate::auth;
-    auth::logged_in().await?;
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
 
     //DB connection
     use crate::app::ssr::*;
     let pool = pool()?;
 
-    let new_id = sqlx::query_scalar(
-        "SELECT id FROM(
-            SELECT id,
-            ROW_NUMBER () OVER ( 
-                ORDER BY uploadDate DESC
-            ) RowNum
-            FROM files)
-        WHERE RowNum = (SELECT RowNum+? FROM 
-                (SELECT id,
-                ROW_NUMBER () OVER ( 
-                    ORDER BY uploadDate DESC
-                ) RowNum
-                FROM files)
-        WHERE id = ?);",
-    )
-    .bind(offset)
-    .bind(prev_id)
-    .fetch_optional(&pool)
-    .await?;
+    if offset == 0 {
+        return Ok(Some(prev_id));
+    }
+
+    let sort = image_filter::feed_order(&filter);
+    let Some(position) = sort.position(&prev_id, &pool).await? else {
+        return Ok(None);
+    };
+
+    let (mut conditions, joins, mut binds) = image_filter::prepare_filtered_query(&filter).await?;
+
+    // Only step through files the user is allowed to see
+    let (access, access_binds) = image_filter::access_condition(user.id);
+    conditions.push(access);
+    binds.extend(access_binds);
+
+    let order_by = if offset > 0 {
+        conditions.push(sort.after());
+        sort.order_by()
+    } else {
+        conditions.push(sort.before());
+        sort.reverse_order_by()
+    };
+    binds.extend(sort.binds.iter().cloned());
+    binds.extend(position);
+    binds.push(prev_id);
+    binds.extend(sort.binds.iter().cloned());
+
+    let query = image_filter::build_filtered_query(
+        "SELECT DISTINCT f.id FROM files f".to_string(),
+        conditions,
+        joins,
+        Some(order_by),
+        Some(1),
+        Some(offset.unsigned_abs() as usize - 1),
+    );
+
+    let mut query = sqlx::query_scalar(&query);
+    for bind in binds {
+        query = query.bind(bind);
+    }
+
+    let new_id = query.fetch_optional(&pool).await?;
 
     Ok(new_id)
 }
@@ -132,28 +211,42 @@ pub async fn delete_image(image_id: String) -> Result<(), ServerFnError> {
 pub fn HomePage() -> impl IntoView {
     let (showing_upload, set_showing_upload) = create_signal(false);
     let (image_id, set_image_id) = create_signal(None);
-    let next_image_id = create_local_resource(image_id, |prev_id| async move {
-        if prev_id.is_some() {
-            match next_prev_image_id(prev_id.unwrap(), 1).await {
-                Ok(Some(id)) => Some(id),
-                Ok(None) => None,
-                Err(_err) => None,
-            }
-        } else {
-            None
-        }
+    //Person pages link to the feed with ?person=<id>
+    let person = use_query_map()
+        .with_untracked(|query| query.get("person").and_then(|id| id.parse::<i64>().ok()));
+    let (filter, set_filter) = create_signal(Filters {
+        people: person.map(|id| ("HAS".to_string(), vec![id])),
+        ..Default::default()
     });
-    let prev_image_id = create_local_resource(image_id, |prev_id| async move {
-        if prev_id.is_some() {
-            match next_prev_image_id(prev_id.unwrap(), -1).await {
-                Ok(Some(id)) => Some(id),
-                Ok(None) => None,
-                Err(_err) => None,
+    //The viewer steps through the filtered feed
+    let next_image_id = create_local_resource(
+        move || (image_id.get(), filter.get()),
+        |(prev_id, filter)| async move {
+            if prev_id.is_some() {
+                match next_prev_image_id(prev_id.unwrap(), 1, filter).await {
+                    Ok(Some(id)) => Some(id),
+                    Ok(None) => None,
+                    Err(_err) => None,
+                }
+            } else {
+                None
             }
-        } else {
-            None
-        }
-    });
+        },
+    );
+    let prev_image_id = create_local_resource(
+        move || (image_id.get(), filter.get()),
+        |(prev_id, filter)| async move {
+            if prev_id.is_some() {
+                match next_prev_image_id(prev_id.unwrap(), -1, filter).await {
+                    Ok(Some(id)) => Some(id),
+                    Ok(None) => None,
+                    Err(_err) => None,
+                }
+            } else {
+                None
+            }
+        },
+    );
 
     let (del_image_from_feed, set_del_image_from_feed) = create_signal(None::<String>);
 
@@ -184,16 +277,30 @@ pub fn HomePage() -> impl IntoView {
 
     let filter_options = vec!["HAS".to_string(), "ONLY".to_string(), "NOT".to_string()];
 
-    let (filter, set_filter) = create_signal(Filters {
-        tags: None,
-        people: None,
-    });
+    let (showing_albums, set_showing_albums) = create_signal(false);
+    let (arranging, set_arranging) = create_signal(false);
+    let (selecting, set_selecting) = create_signal(false);
+    let selected = create_rw_signal(HashSet::<String>::new());
+    let albums = create_resource(|| (), |_| async { get_albums().await.unwrap_or_default() });
 
     let users = create_rw_signal(vec![]);
+    let uploaders = create_rw_signal(vec![]);
     let tags = create_rw_signal(vec![]);
+    let places = create_rw_signal(vec![]);
+    let selected_users = create_rw_signal(vec![]);
     spawn_local(async move {
-        match crate::components::users::get_user_list_sans_admin().await {
-            Ok(m) => users.set(m),
+        match crate::components::people::get_people().await {
+            Ok(p) => {
+                if let Some((_, ids)) = filter.get_untracked().people {
+                    selected_users.set(p.iter().filter(|p| ids.contains(&p.id)).cloned().collect());
+                }
+                users.set(p);
+            }
+            Err(e) => logging::log!("{}", e),
+        };
+
+        match crate::components::users::get_uploaders().await {
+            Ok(u) => uploaders.set(u),
             Err(e) => logging::log!("{}", e),
         };
 
@@ -202,15 +309,116 @@ pub fn HomePage() -> impl IntoView {
             Err(e) => logging::log!("{}", e),
         };
 
+        match crate::components::map::get_places().await {
+            Ok(p) => places.set(p),
+            Err(e) => logging::log!("{}", e),
+        };
+
         logging::log!("{:?}", tags.get_untracked());
     });
 
     let select_ref = create_node_ref::<Select>();
     let select_ref_2 = create_node_ref::<Select>();
-
-    let selected_users = create_rw_signal(vec![]);
+    let uploader_mode_ref = create_node_ref::<Select>();
+    let place_mode_ref = create_node_ref::<Select>();
+    let album_ref = create_node_ref::<Select>();
+    let order_ref = create_node_ref::<Select>();
+    let query_ref = create_node_ref::<Input>();
+    let search_ref = create_node_ref::<Input>();
+    let from_ref = create_node_ref::<Input>();
+    let to_ref = create_node_ref::<Input>();
+    let near_ref = create_node_ref::<Input>();
+    let km_ref = create_node_ref::<Input>();
+    let (showing_map, set_showing_map) = create_signal(false);
+    let (query_error, set_query_error) = create_signal(None::<String>);
+
+    let selected_uploaders = create_rw_signal(vec![]);
+    let selected_places = create_rw_signal(vec![]);
     let selected_tags = create_rw_signal(vec![]);
 
+    // Show a saved filter in the filter bar and apply it to the feed
+    let apply_filter = move |f: Filters| {
+        if let Some((mode, _)) = &f.tags {
+            select_ref.get_untracked().unwrap().set_value(mode);
+        }
+        selected_tags.set(
+            f.tags
+                .clone()
+                .map(|(_, tags)| {
+                    tags.into_iter()
+                        .map(|tag_string| Tag { tag_string })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        );
+
+        if let Some((mode, _)) = &f.people {
+            select_ref_2.get_untracked().unwrap().set_value(mode);
+        }
+        let people_ids = f.people.clone().map(|(_, ids)| ids).unwrap_or_default();
+        selected_users.set(
+            users
+                .get_untracked()
+                .into_iter()
+                .filter(|p: &crate::components::people::PersonInfo| people_ids.contains(&p.id))
+                .collect(),
+        );
+
+        if let Some((mode, _)) = &f.uploader {
+            uploader_mode_ref.get_untracked().unwrap().set_value(mode);
+        }
+        let uploader_ids = f.uploader.clone().map(|(_, ids)| ids).unwrap_or_default();
+        selected_uploaders.set(
+            uploaders
+                .get_untracked()
+                .into_iter()
+                .filter(|u: &crate::components::users::UserInfo| uploader_ids.contains(&u.id))
+                .collect(),
+        );
+
+        if let Some((mode, _)) = &f.places {
+            place_mode_ref.get_untracked().unwrap().set_value(mode);
+        }
+        selected_places.set(f.places.clone().map(|(_, p)| p).unwrap_or_default());
+
+        album_ref
+            .get_untracked()
+            .unwrap()
+            .set_value(&f.album.clone().unwrap_or_default());
+        order_ref
+            .get_untracked()
+            .unwrap()
+            .set_value(f.order.value());
+        query_ref
+            .get_untracked()
+            .unwrap()
+            .set_value(&f.query.clone().unwrap_or_default());
+        set_query_error(None);
+        search_ref
+            .get_untracked()
+            .unwrap()
+            .set_value(&f.search.clone().unwrap_or_default());
+        from_ref
+            .get_untracked()
+            .unwrap()
+            .set_value(&f.from.clone().unwrap_or_default());
+        to_ref
+            .get_untracked()
+            .unwrap()
+            .set_value(&f.to.clone().unwrap_or_default());
+        if let Some(GeoFilter::Radius { lat, lon, km }) = f.geo {
+            near_ref
+                .get_untracked()
+                .unwrap()
+                .set_value(&format!("{}, {}", lat, lon));
+            km_ref.get_untracked().unwrap().set_value(&km.to_string());
+        } else {
+            near_ref.get_untracked().unwrap().set_value("");
+        }
+
+        set_filter(f);
+    };
+
     view! {
         <button
             class = "floating displayFeed"
@@ -257,8 +465,53 @@ pub fn HomePage() -> impl IntoView {
             <h1>"Upload"</h1>
             <UploadMedia/>
         </Dialog>
+        <Dialog
+            on_close=move || set_showing_albums(false)
+            open=showing_albums>
+            <h1>"Albums"</h1>
+            <AlbumManager on_change=move |_| albums.refetch()/>
+        </Dialog>
+        <Dialog
+            on_close=move || set_arranging(false)
+            open=arranging>
+            <h1>"Arrange album"</h1>
+            {move || filter.get().album.map(|album_id| view! {
+                <AlbumArranger album_id=album_id on_change=move |_| set_filter.update(|_| {})/>
+            })}
+        </Dialog>
 
+        <div class="home-layout">
+        <SavedFilterList filter=filter on_select=apply_filter/>
+        <div class="home-content">
         <div class="horizontal">
+        <select
+            _ref=album_ref
+            on:change=move |ev| {
+                let album = event_target_value(&ev);
+                set_filter.update(|f| f.album = if album.is_empty() {None} else {Some(album)});
+            }>
+            <option value="">"All photos"</option>
+            {move || albums.get().unwrap_or_default().into_iter().map(|album| view! {
+                <option value={album.id}>{album.name}</option>
+            }).collect_view()}
+        </select>
+        <select
+            _ref=order_ref
+            on:change=move |ev| {
+                let order = FeedOrder::from_value(&event_target_value(&ev));
+                set_filter.update(|f| f.order = order);
+            }>
+            <option value=FeedOrder::UploadDate.value()>"Upload date"</option>
+            <option value=FeedOrder::CaptureDate.value()>"Capture date"</option>
+            <option value=FeedOrder::Manual.value() disabled=move || filter.get().album.is_none()>"Album order"</option>
+        </select>
+        <Show when=move || {
+            let album = filter.get().album;
+            albums.get().unwrap_or_default().iter().any(|a| Some(&a.id) == album.as_ref() && a.can_contribute())
+        }>
+            <button on:click=move |_| set_arranging(true)>"Arrange"</button>
+        </Show>
+        <button on:click=move |_| set_showing_albums(true)>"Albums"</button>
         <select
             _ref=select_ref
             >
@@ -271,7 +524,7 @@ pub fn HomePage() -> impl IntoView {
         <Multiselect class="mselect"
             options = tags
             search_text_provider=move |o: Tag| o.tag_string
-            render_option=move |o: Tag| o.tag_string
+            render_option=move |o: Tag| crate::components::tags::render_tree(&o.tag_string)
             selected=selected_tags
             set_selected=move |v| selected_tags.set(v)
         ></Multiselect>
@@ -286,21 +539,47 @@ pub fn HomePage() -> impl IntoView {
         </select>
         <Multiselect class="mselect"
             options = users
-            search_text_provider=move |o: crate::components::users::UserInfo| o.username
-            render_option=move |o: crate::components::users::UserInfo| o.username
+            search_text_provider=move |o: crate::components::people::PersonInfo| o.name
+            render_option=move |o: crate::components::people::PersonInfo| o.name
             selected=selected_users
             add=move |x| logging::log!("{}", x)
             set_selected=move |v| selected_users.set(v)
         ></Multiselect>
+        <select
+            _ref=uploader_mode_ref
+            >
+            <option value="HAS">"UPLOADED BY"</option>
+            <option value="NOT">"NOT UPLOADED BY"</option>
+        </select>
+        <Multiselect class="mselect"
+            options = uploaders
+            search_text_provider=move |o: crate::components::users::UserInfo| o.username
+            render_option=move |o: crate::components::users::UserInfo| o.username
+            selected=selected_uploaders
+            set_selected=move |v| selected_uploaders.set(v)
+        ></Multiselect>
+        <select
+            _ref=place_mode_ref
+            >
+            <option value="HAS">"TAKEN IN"</option>
+            <option value="NOT">"NOT TAKEN IN"</option>
+        </select>
+        <Multiselect class="mselect"
+            options = places
+            search_text_provider=move |o: PlaceName| o.name
+            render_option=move |o: PlaceName| o.render_tree()
+            selected=selected_places
+            set_selected=move |v| selected_places.set(v)
+        ></Multiselect>
         <button
             on:click=move |_| {
-                let filter = select_ref.get().unwrap().value();
-                let filter_2 = select_ref_2.get().unwrap().value();
+                let tag_mode = select_ref.get().unwrap().value();
+                let people_mode = select_ref_2.get().unwrap().value();
                 let i_tags: Vec<String> = selected_tags.get_untracked().into_iter().map(|x: Tag| x.tag_string).collect();
-                let i_people: Vec<i64> = selected_users.get_untracked().into_iter().map(|x: crate::components::users::UserInfo| x.id).collect();
+                let i_people: Vec<i64> = selected_users.get_untracked().into_iter().map(|x: crate::components::people::PersonInfo| x.id).collect();
 
-                let mut valid_tag_filter: Option<(String, Vec<String>)> = Some((filter, i_tags.clone()));
-                let mut valid_people_filter: Option<(String, Vec<i64>)> = Some((filter_2, i_people.clone()));
+                let mut valid_tag_filter: Option<(String, Vec<String>)> = Some((tag_mode, i_tags.clone()));
+                let mut valid_people_filter: Option<(String, Vec<i64>)> = Some((people_mode, i_people.clone()));
 
 
                 if i_tags.is_empty() || i_tags.iter().all(|tag| tag == ""){
@@ -311,20 +590,128 @@ pub fn HomePage() -> impl IntoView {
                     valid_people_filter = None;
                 }
 
-                set_filter(Filters {
-                    tags: valid_tag_filter,
-                    people: valid_people_filter,
+                let uploader_mode = uploader_mode_ref.get().unwrap().value();
+                let i_uploaders: Vec<i64> = selected_uploaders.get_untracked().into_iter().map(|x: crate::components::users::UserInfo| x.id).collect();
+                let valid_uploader_filter = if i_uploaders.is_empty() {None} else {Some((uploader_mode, i_uploaders))};
+
+                let place_mode = place_mode_ref.get().unwrap().value();
+                let i_places: Vec<PlaceName> = selected_places.get_untracked();
+                let valid_place_filter = if i_places.is_empty() {None} else {Some((place_mode, i_places))};
+
+                let query = query_ref.get().unwrap().value();
+                if !query.trim().is_empty() {
+                    if let Err(e) = crate::filter_query::parse(&query) {
+                        set_query_error(Some(e.to_string()));
+                        return;
+                    }
+                }
+                set_query_error(None);
+
+                set_filter.update(|f| {
+                    f.tags = valid_tag_filter;
+                    f.people = valid_people_filter;
+                    f.uploader = valid_uploader_filter;
+                    f.places = valid_place_filter;
+                    f.query = if query.trim().is_empty() {None} else {Some(query)};
                 });
 
             }
             >"Filter"
         </button>
         </div>
-        <InfiniteFeed
-            on_image_click=move |image_id:String|
-            set_image_id(Some(image_id))
-            send_id=del_image_from_feed
-            filter=filter
-        />
+        <div class="horizontal">
+        <input type="search" class="query-input"
+            placeholder="Search captions, tags, people and places"
+            _ref=search_ref
+            on:change=move |ev| {
+                let search = event_target_value(&ev);
+                set_filter.update(|f| f.search = if search.trim().is_empty() {None} else {Some(search)});
+            }/>
+        <label>"From "
+            <input type="date" _ref=from_ref on:change=move |ev| {
+                let date = event_target_value(&ev);
+                set_filter.update(|f| f.from = if date.is_empty() {None} else {Some(date)});
+            }/>
+        </label>
+        <label>"To "
+            <input type="date" _ref=to_ref on:change=move |ev| {
+                let date = event_target_value(&ev);
+                set_filter.update(|f| f.to = if date.is_empty() {None} else {Some(date)});
+            }/>
+        </label>
+        <input type="text" class="query-input"
+            placeholder="person:anna AND (tag:beach OR tag:lake) AND NOT tag:work"
+            _ref=query_ref
+            on:input=move |ev| {
+                let query = event_target_value(&ev);
+                set_query_error(match crate::filter_query::parse(&query) {
+                    Err(e) if !query.trim().is_empty() => Some(e.to_string()),
+                    _ => None,
+                });
+            }/>
+        <Show when=move || query_error().is_some()>
+            <span class="query-error">{query_error().unwrap()}</span>
+        </Show>
+        </div>
+        <div class="horizontal">
+        <input type="text" placeholder="Near 59.91, 10.75" _ref=near_ref/>
+        <input type="number" class="km-input" min="0" step="any" value="10" _ref=km_ref/>
+        <span>"km"</span>
+        <button on:click=move |_| {
+            let point = parse_point(&near_ref.get_untracked().unwrap().value());
+            let km = km_ref.get_untracked().unwrap().value().parse::<f64>().ok().filter(|km| *km > 0.0);
+            if let (Some((lat, lon)), Some(km)) = (point, km) {
+                set_filter.update(|f| f.geo = Some(GeoFilter::Radius { lat, lon, km }));
+            }
+        }>"Near"</button>
+        <Show when=move || filter.get().geo.is_some()>
+            <button on:click=move |_| {
+                near_ref.get_untracked().unwrap().set_value("");
+                set_filter.update(|f| f.geo = None);
+            }>"Any place"</button>
+        </Show>
+        <button on:click=move |_| set_showing_map.update(|m| *m = !*m)>
+            {move || if showing_map.get() {"Feed"} else {"Map"}}
+        </button>
+        </div>
+        <div class="horizontal">
+        <button on:click=move |_| {
+            set_selecting.update(|s| *s = !*s);
+            selected.set(HashSet::new());
+        }>{move || if selecting.get() {"Done selecting"} else {"Select"}}</button>
+        <Show when=selecting>
+            <BulkActions
+                selected=selected
+                album=Signal::derive(move || filter.get().album)
+                on_done=move |_| set_filter.update(|_| {})/>
+        </Show>
+        </div>
+        //Memories on the unfiltered feed
+        <Show when=move || !showing_map.get() && filter.get() == Filters::default()>
+            <OnThisDay on_image_click=move |image_id: String| set_image_id(Some(image_id))/>
+        </Show>
+        <Show
+            when=showing_map
+            fallback=move || view! {
+                <InfiniteFeed
+                    on_image_click=move |image_id:String|
+                    set_image_id(Some(image_id))
+                    send_id=del_image_from_feed
+                    filter=filter
+                    selecting=selecting
+                    selected=selected
+                />
+            }>
+            <PhotoMap
+                filter=filter
+                on_image_click=move |image_id: String| set_image_id(Some(image_id))
+                on_area=move |area| {
+                    near_ref.get_untracked().unwrap().set_value("");
+                    set_filter.update(|f| f.geo = Some(area));
+                    set_showing_map(false);
+                }/>
+        </Show>
+        </div>
+        </div>
     }
 }
@@ -1,16 +1,19 @@
 #[cfg(feature = "ssr")]
 use crate::auth;
 use crate::auth::User;
+use crate::components::albums::ImageAlbums;
 use crate::components::dialog::Dialog;
+use crate::components::faces::UnnamedFaces;
+use crate::components::people::get_people;
+use crate::components::share::ShareLinks;
 #[cfg(feature = "ssr")]
 use crate::components::upload::Bbox;
 use crate::components::upload::Person;
 use crate::components::upload::{decode_image, img_from_bounds};
-use crate::components::users::get_user_list_sans_admin;
 use image::DynamicImage;
 use leptonic::components::icon::Icon;
 use leptonic::components::prelude::OptionalSelect;
-use leptos::html::Input;
+use leptos::html::{Input, Textarea};
 use leptos::*;
 use serde::Deserialize;
 use serde::Serialize;
@@ -31,6 +34,8 @@ pub struct ImageDb {
     created_date: Option<String>,
     uploader: String,
     location: Option<String>,
+    caption: Option<String>,
+    place: Option<String>,
 }
 impl ImageDb {
     pub fn into_info(self) -> ImageInfo {
@@ -40,6 +45,8 @@ impl ImageDb {
             created_date: self.created_date,
             uploader: self.uploader,
             location: self.location,
+            caption: self.caption,
+            place: self.place,
         }
     }
 }
@@ -51,6 +58,9 @@ pub struct ImageInfo {
     created_date: Option<String>,
     uploader: String,
     location: Option<String>,
+    caption: Option<String>,
+    /// City, region and country from the gazetteer
+    place: Option<String>,
 }
 impl Default for ImageInfo {
     fn default() -> Self {
@@ -60,6 +70,8 @@ impl Default for ImageInfo {
             created_date: None,
             uploader: "".into(),
             location: None,
+            caption: None,
+            place: None,
         }
     }
 }
@@ -97,20 +109,26 @@ impl<'a> FromRow<'a, SqliteRow> for Person {
 //Fetch images from database
 #[server(Image, "/api")]
 pub async fn get_image(image_id: String) -> Result<ImageDb, ServerFnError> {
-    auth::logged_in().await?;
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
 
     //DB connection
     use crate::app::ssr::*;
     let pool = pool()?;
 
-    //Fetch image
-    let mut img = sqlx::query_as::<_, ImageDb>(
-        "SELECT files.id, path, uploadDate AS upload_date, createdDate AS created_date, users.username AS uploader, location 
-        FROM files INNER JOIN users ON files.uploadedBy=users.id WHERE files.id = ?;",
-    )
-    .bind(image_id)
-    .fetch_one(&pool)
-    .await?;
+    //Fetch image, unless it is hidden in a private album
+    let (access, access_binds) = image_filter::access_condition(user.id);
+    let query = format!(
+        "SELECT f.id, path, uploadDate AS upload_date, createdDate AS created_date, users.username AS uploader, location, caption,
+            CASE WHEN f.country IS NULL THEN NULL ELSE f.city || ', ' || f.region || ', ' || f.country END AS place
+        FROM files f INNER JOIN users ON f.uploadedBy=users.id WHERE f.id = ? AND {};",
+        access
+    );
+    let mut query = sqlx::query_as::<_, ImageDb>(&query).bind(image_id);
+    for bind in access_binds {
+        query = query.bind(bind);
+    }
+    let mut img = query.fetch_one(&pool).await?;
 
     // Read the image file
     let mut file = File::open(&img.path).expect("Failed to open image file");
@@ -127,6 +145,28 @@ pub async fn get_image(image_id: String) -> Result<ImageDb, ServerFnError> {
     Ok(img)
 }
 
+//Only the uploader or an admin can change an image
+#[cfg(feature = "ssr")]
+async fn authorize_change(
+    image_id: &str,
+    user: &User,
+    pool: &sqlx::SqlitePool,
+) -> Result<(), ServerFnError> {
+    let uploader: bool = sqlx::query_scalar("SELECT uploadedBy=? FROM files WHERE id = ?")
+        .bind(user.id)
+        .bind(image_id)
+        .fetch_one(pool)
+        .await?;
+
+    if !uploader && !user.has("admin") {
+        return Err(ServerFnError::ServerError(
+            "You are not authorized, only the uploader can change an image".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
 //Update image info
 #[server(UpdateImageInfo, "/api")]
 pub async fn update_image_info(
@@ -140,18 +180,7 @@ pub async fn update_image_info(
     use crate::app::ssr::*;
     let pool = pool()?;
 
-    //only uploader or admin
-    let uploader: bool = sqlx::query_scalar("SELECT uploadedBy=? FROM files WHERE id = ?")
-        .bind(user.id)
-        .bind(image_id.clone())
-        .fetch_one(&pool)
-        .await?;
-
-    if !uploader && !user.has("admin") {
-        return Err(ServerFnError::ServerError(
-            "You are not authorized, only the uploader can change an image".to_string(),
-        ));
-    }
+    authorize_change(&image_id, &user, &pool).await?;
 
     //Check if created_date is a valid date
     use regex::Regex;
@@ -177,21 +206,49 @@ pub async fn update_image_info(
     Ok(())
 }
 
+//Set the caption of an image, an empty caption removes it
+#[server(SetCaption, "/api")]
+pub async fn set_caption(image_id: String, caption: String) -> Result<(), ServerFnError> {
+    let user = auth::logged_in().await?;
+
+    //DB connection
+    use crate::app::ssr::*;
+    let pool = pool()?;
+
+    authorize_change(&image_id, &user, &pool).await?;
+
+    let caption = caption.trim();
+    sqlx::query("UPDATE files SET caption=? WHERE id = ?;")
+        .bind((!caption.is_empty()).then_some(caption))
+        .bind(image_id)
+        .execute(&pool)
+        .await?;
+
+    Ok(())
+}
+
 //Fetch images from database
 #[server(UseresInImage, "/api")]
 pub async fn get_users_in_image(image_id: String) -> Result<Vec<Person>, ServerFnError> {
-    auth::logged_in().await?;
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
 
     //DB connection
     use crate::app::ssr::*;
     let pool = pool()?;
 
-    //Fetch users
+    if !image_filter::can_view_file(&image_id, user.id, &pool).await? {
+        return Err(ServerFnError::ServerError(
+            "You are not authorized to view this image".to_string(),
+        ));
+    }
+
+    //Fetch people
     let users = sqlx::query_as::<_, Person>(
-        "SELECT x, y, width AS w, height AS h, users.username AS name, users.id as id 
-        FROM users 
+        "SELECT x, y, width AS w, height AS h, people.name AS name, people.id as id 
+        FROM people 
         JOIN userFile 
-        ON users.id = userFile.userID 
+        ON people.id = userFile.personID 
         WHERE userFile.fileID = ?;",
     )
     .bind(image_id)
@@ -209,12 +266,19 @@ pub async fn update_users_in_image(
     #[server(default)] users_delete: Vec<i64>,
     #[server(default)] users_add: Vec<Person>,
 ) -> Result<Vec<Person>, ServerFnError> {
-    auth::logged_in().await?;
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
 
     //DB connection
     use crate::app::ssr::*;
     let pool = pool()?;
 
+    if !image_filter::can_view_file(&image_id, user.id, &pool).await? {
+        return Err(ServerFnError::ServerError(
+            "You are not authorized to edit this image".to_string(),
+        ));
+    }
+
     println!("{:?},{:?},{:?}", users_change, users_delete, users_add);
 
     //Update UserFile
@@ -222,7 +286,7 @@ pub async fn update_users_in_image(
     let mut array = "?,".repeat(users_delete.len());
     array.pop();
     let query = format!(
-        "DELETE FROM userFile WHERE fileID=? and userID IN ({});",
+        "DELETE FROM userFile WHERE fileID=? and personID IN ({});",
         array
     );
     let mut q = sqlx::query(query.as_str()).bind(&image_id);
@@ -236,17 +300,7 @@ pub async fn update_users_in_image(
             continue; // Skip this person.
         }
 
-        let user = match auth::ssr::SqlUser::get_from_username(person.name.clone(), &pool).await {
-            Some(u) => u.id,
-            None => {
-                let res = sqlx::query("INSERT OR IGNORE INTO users (username) VALUES (?)")
-                    .bind(person.name)
-                    .execute(&pool)
-                    .await?;
-
-                res.last_insert_rowid()
-            }
-        };
+        let person_id = crate::components::people::ssr::person_id(&person.name, &pool).await?;
         let binds: Vec<Option<u32>> = match person.bounds {
             Some(b) => vec![b.x, b.y, b.w, b.h]
                 .into_iter()
@@ -254,12 +308,15 @@ pub async fn update_users_in_image(
                 .collect(),
             None => vec![None::<u32>; 4],
         };
+        //The embedding belongs to the old box
         let mut q = sqlx::query(
-            "UPDATE userFile SET userID = ?, x=?, y=?, width=?, height=? WHERE userID = ? and fileID = ?",
+            "UPDATE userFile SET personID = ?,
+            embedding = CASE WHEN x IS ? AND y IS ? AND width IS ? AND height IS ? THEN embedding END,
+            x=?, y=?, width=?, height=? WHERE personID = ? and fileID = ?",
         )
-        .bind(user);
-        for b in binds {
-            q = q.bind(b);
+        .bind(person_id);
+        for b in binds.iter().chain(&binds) {
+            q = q.bind(*b);
         }
         q.bind(old_id).bind(&image_id).execute(&pool).await?;
     }
@@ -269,17 +326,7 @@ pub async fn update_users_in_image(
             continue; // Skip this person.
         }
 
-        let user = match auth::ssr::SqlUser::get_from_username(person.name.clone(), &pool).await {
-            Some(u) => u.id,
-            None => {
-                let res = sqlx::query("INSERT OR IGNORE INTO users (username) VALUES (?)")
-                    .bind(person.name)
-                    .execute(&pool)
-                    .await?;
-
-                res.last_insert_rowid()
-            }
-        };
+        let person_id = crate::components::people::ssr::person_id(&person.name, &pool).await?;
         let binds: Vec<Option<u32>> = match person.bounds {
             Some(b) => vec![b.x, b.y, b.w, b.h]
                 .into_iter()
@@ -288,9 +335,9 @@ pub async fn update_users_in_image(
             None => vec![None::<u32>; 4],
         };
         let mut q = sqlx::query(
-            "INSERT OR REPLACE INTO userFile (userID, fileID, x, y, width, height) VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT OR REPLACE INTO userFile (personID, fileID, x, y, width, height) VALUES (?, ?, ?, ?, ?, ?)",
         )
-        .bind(user)
+        .bind(person_id)
         .bind(&image_id);
         for b in binds {
             q = q.bind(b);
@@ -298,6 +345,15 @@ pub async fn update_users_in_image(
         q.execute(&pool).await?;
     }
 
+    //New and moved boxes get an embedding for face suggestions
+    if let Some(detectors) = use_context::<std::sync::Arc<crate::face_detector::FaceDetectors>>() {
+        if let Err(e) =
+            crate::components::faces::ssr::embed_marked_faces(&image_id, &detectors, &pool).await
+        {
+            eprintln!("Face embedding failed for {}: {}", image_id, e);
+        }
+    }
+
     get_users_in_image(image_id).await
 }
 
@@ -329,6 +385,8 @@ where
 
     let (delete_prompt, set_delete_prompt) = create_signal(false);
 
+    let (sharing, set_sharing) = create_signal(false);
+
     view! {
         <Suspense fallback = move|| view!{
             <div class="img_alt">
@@ -358,12 +416,18 @@ where
                     </div>
                     <div class="upload-info">
                         <h3>"Image info:"</h3>
+                        <Show when=move || image_info().caption.is_some()>
+                            <p class="caption">{move || image_info().caption.unwrap_or_default()}</p>
+                        </Show>
                         <span><Icon class="icon" icon=icondata::FaCameraSolid/>
                             {move ||{if let Some(date) = image_info().created_date {date}else{empty()}}}
                         </span>
                         <span><Icon class="icon" icon=icondata::BiMapSolid/>
                             {move ||if let Some(location) = image_info().location {location}else{empty()}}
                         </span>
+                        <Show when=move || image_info().place.is_some()>
+                            <span class="place">{move || image_info().place.unwrap_or_default()}</span>
+                        </Show>
                         {
                             let disable = move ||{
                                 if image_info().id.is_empty(){
@@ -397,6 +461,7 @@ where
                                         if let Some(ref mut img)= &mut img{
                                             img.created_date=new_image_info.created_date.clone();
                                             img.location=new_image_info.location.clone();
+                                            img.caption=new_image_info.caption.clone();
                                         };
                                     });
                                 }/>
@@ -405,6 +470,7 @@ where
                             ().into_view()
                         }}
                     </div>
+                    <ImageAlbums image_id=image_id/>
                     <div class="upload-info">
                         <h3>"Uploaded by:"</h3>
                         <span><Icon class="icon" icon=icondata::BiUserCircleSolid/>
@@ -448,6 +514,31 @@ where
                                 }
                             }
                         }
+                        {
+                            let disable = move||{
+                                let user = use_context::<User>();
+                                if let Some(user) = user{
+                                    return user.username != image_info().uploader &&
+                                        !user.has("admin");
+                                }
+                                return true
+                            };
+                            view!{
+                                <button
+                                    class:hastooltip=disable
+                                    disabled=disable
+                                    on:click=move |_| {set_sharing(true)}>
+                                    <span class="tooltiptext">"You can only share your own images"</span>
+                                    "Share"
+                                </button>
+                            }
+                        }
+                        <Dialog
+                            on_close=move || set_sharing(false)
+                            open=sharing
+                            small=true>
+                            {move || view!{<ShareLinks file_id=Some(image_id()) album_id=None/>}}
+                        </Dialog>
                     </div>
                 </div>
             </div>
@@ -503,6 +594,9 @@ where
                             set_editing_people(true);
                         }><Icon class="icon" icon=icondata::FaPenSolid/>
                     </button>
+                    <UnnamedFaces
+                    file_id=image_id()
+                    on_named=move |_| people.refetch()/>
                     <UserInImageEdit
                     image_id=image_id()
                     img=image.clone()
@@ -537,12 +631,8 @@ where
 {
     let users = create_rw_signal(vec![]);
     spawn_local(async move {
-        match get_user_list_sans_admin().await {
-            Ok(u) => users.set(
-                u.iter()
-                    .map(|user_info| user_info.username.clone())
-                    .collect(),
-            ),
+        match get_people().await {
+            Ok(p) => users.set(p.iter().map(|person| person.name.clone()).collect()),
             Err(_) => (),
         };
     });
@@ -763,6 +853,7 @@ where
     let (update_error, set_update_error) = create_signal(None);
     let input_location = create_node_ref::<Input>();
     let input_created_date = create_node_ref::<Input>();
+    let input_caption = create_node_ref::<Textarea>();
 
     let on_close_clone = on_close.clone();
     let on_close_click = move |_| on_close_clone();
@@ -784,8 +875,17 @@ where
         } else {
             Some(node_created_date.value())
         };
+        let node_caption = input_caption.get().expect("ref should be loaded by now");
+        let caption = if node_caption.value().trim().is_empty() {
+            None
+        } else {
+            Some(node_caption.value())
+        };
         //check for changes
-        if image_clone.created_date == created_date && image_clone.location == location {
+        if image_clone.created_date == created_date
+            && image_clone.location == location
+            && image_clone.caption == caption
+        {
             on_close_clone();
             return;
         }
@@ -794,11 +894,22 @@ where
         let mut new_img = image_clone.clone();
         new_img.created_date = created_date.clone();
         new_img.location = location.clone();
+        new_img.caption = caption.clone();
         let image_id = image_clone.id.clone();
         let update_image = update_image.clone();
         let on_close = on_close_clone.clone();
+        let info_changed =
+            image_clone.created_date != created_date || image_clone.location != location;
+        let caption_changed = image_clone.caption != caption;
         spawn_local(async move {
-            match update_image_info(image_id.clone(), created_date, location).await {
+            let mut result = Ok(());
+            if info_changed {
+                result = update_image_info(image_id.clone(), created_date, location).await;
+            }
+            if result.is_ok() && caption_changed {
+                result = set_caption(image_id.clone(), caption.unwrap_or_default()).await;
+            }
+            match result {
                 Ok(_) => {
                     set_updating_image_info(false);
                     update_image(new_img);
@@ -838,6 +949,13 @@ where
                     name="loaction"
                 />
                 <br/>
+                <label for="caption"><Icon class="icon" icon=icondata::FaPenSolid/>Caption</label>
+                <textarea
+                    _ref=input_caption
+                    name="caption"
+                    prop:value={image.caption.clone().unwrap_or_default()}
+                ></textarea>
+                <br/>
                 <Show when=move||{update_error().is_some()}>
                     <span>{format!("An Error occured{}", update_error().unwrap())}</span>
                 </Show>
@@ -0,0 +1,401 @@
+#[cfg(feature = "ssr")]
+use crate::auth;
+use crate::components::home_page::Filters;
+use crate::gazetteer::{place_tree, PlaceName};
+use leptos::html::Div;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+//Length of one degree of latitude
+pub const KM_PER_DEGREE: f64 = 111.32;
+
+//Clusters per map tile side, a tile is 256 pixels wide
+const CLUSTERS_PER_TILE: f64 = 4.0;
+
+//Where photos were taken
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GeoFilter {
+    /// Within `km` of a point
+    Radius { lat: f64, lon: f64, km: f64 },
+    /// Inside a box. `west` is larger than `east` when the box crosses the antimeridian.
+    BoundingBox {
+        south: f64,
+        west: f64,
+        north: f64,
+        east: f64,
+    },
+}
+
+impl GeoFilter {
+    /// SQL condition on `f.latitude` and `f.longitude` with its binds. Photos without
+    /// coordinates never match.
+    pub fn to_sql(&self) -> (String, Vec<String>) {
+        // Binds are text, which SQLite would compare as larger than any number
+        let real = "CAST(? AS REAL)";
+
+        match *self {
+            GeoFilter::BoundingBox {
+                south,
+                west,
+                north,
+                east,
+            } => {
+                let longitude = if west <= east { "AND" } else { "OR" };
+                (
+                    format!(
+                        "(f.latitude BETWEEN {0} AND {0} AND (f.longitude >= {0} {1} f.longitude <= {0}))",
+                        real, longitude
+                    ),
+                    [south, north, west, east].map(|v| v.to_string()).to_vec(),
+                )
+            }
+            // Bounding box first so the index is used, then distance on an
+            // equirectangular projection. Plenty accurate at photo album distances.
+            GeoFilter::Radius { lat, lon, km } => {
+                let (south, west, north, east) = self.bounds();
+                let degrees = km / KM_PER_DEGREE;
+                (
+                    format!(
+                        "(f.latitude BETWEEN {0} AND {0} AND f.longitude BETWEEN {0} AND {0}
+                        AND (f.latitude - {0}) * (f.latitude - {0})
+                            + (f.longitude - {0}) * {0} * (f.longitude - {0}) * {0} <= {0})",
+                        real
+                    ),
+                    [
+                        south,
+                        north,
+                        west,
+                        east,
+                        lat,
+                        lat,
+                        lon,
+                        lat.to_radians().cos(),
+                        lon,
+                        lat.to_radians().cos(),
+                        degrees * degrees,
+                    ]
+                    .map(|v| v.to_string())
+                    .to_vec(),
+                )
+            }
+        }
+    }
+
+    /// South, west, north and east edges. A radius does not wrap around the antimeridian.
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        match *self {
+            GeoFilter::BoundingBox {
+                south,
+                west,
+                north,
+                east,
+            } => (south, west, north, east),
+            GeoFilter::Radius { lat, lon, km } => {
+                let dlat = km / KM_PER_DEGREE;
+                let cos = lat.to_radians().cos();
+                let dlon = if cos * 180.0 > dlat {
+                    dlat / cos
+                } else {
+                    180.0
+                };
+                (
+                    (lat - dlat).max(-90.0),
+                    (lon - dlon).max(-180.0),
+                    (lat + dlat).min(90.0),
+                    (lon + dlon).min(180.0),
+                )
+            }
+        }
+    }
+}
+
+/// Parse `lat, lon` in decimal degrees.
+pub fn parse_point(point: &str) -> Option<(f64, f64)> {
+    let (lat, lon) = point.split_once(',')?;
+    let lat: f64 = lat.trim().parse().ok()?;
+    let lon: f64 = lon.trim().parse().ok()?;
+
+    if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) {
+        Some((lat, lon))
+    } else {
+        None
+    }
+}
+
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub id: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cluster {
+    /// Average position of the photos
+    pub lat: f64,
+    pub lon: f64,
+    pub count: usize,
+    /// First photo of the cluster
+    pub photo: String,
+}
+
+/// Group points into grid cells sized for the map zoom level.
+pub fn cluster(points: &[GeoPoint], zoom: u8) -> Vec<Cluster> {
+    let cell = 360.0 / 2f64.powi(zoom.min(20) as i32) / CLUSTERS_PER_TILE;
+
+    let mut cells: BTreeMap<(i64, i64), Cluster> = BTreeMap::new();
+    for point in points {
+        let key = (
+            (point.lat / cell).floor() as i64,
+            (point.lon / cell).floor() as i64,
+        );
+        let cluster = cells.entry(key).or_insert_with(|| Cluster {
+            lat: 0.0,
+            lon: 0.0,
+            count: 0,
+            photo: point.id.clone(),
+        });
+        cluster.lat += point.lat;
+        cluster.lon += point.lon;
+        cluster.count += 1;
+    }
+
+    cells
+        .into_values()
+        .map(|mut cluster| {
+            cluster.lat /= cluster.count as f64;
+            cluster.lon /= cluster.count as f64;
+            cluster
+        })
+        .collect()
+}
+
+/// GeoJSON feature collection with a point per cluster.
+pub fn to_geojson(clusters: &[Cluster]) -> serde_json::Value {
+    serde_json::json!({
+        "type": "FeatureCollection",
+        "features": clusters.iter().map(|cluster| serde_json::json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": [cluster.lon, cluster.lat],
+            },
+            "properties": {
+                "count": cluster.count,
+                "photo": cluster.photo,
+            },
+        })).collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use exif::{In, Reader, Tag, Value};
+    use std::io::Cursor;
+
+    /// GPS position from the EXIF data of an image, if it has any.
+    pub fn exif_coordinates(bytes: &[u8]) -> Option<(f64, f64)> {
+        let exif = Reader::new()
+            .read_from_container(&mut Cursor::new(bytes))
+            .ok()?;
+
+        let degrees = |tag: Tag, reference: Tag, negative: u8| -> Option<f64> {
+            let value = match &exif.get_field(tag, In::PRIMARY)?.value {
+                Value::Rational(parts) if parts.len() == 3 => {
+                    parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0
+                }
+                _ => return None,
+            };
+            let negative = match &exif.get_field(reference, In::PRIMARY)?.value {
+                Value::Ascii(refs) => refs.first()?.first() == Some(&negative),
+                _ => false,
+            };
+
+            Some(if negative { -value } else { value })
+        };
+
+        let lat = degrees(Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S')?;
+        let lon = degrees(Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W')?;
+
+        if lat.is_finite() && lon.is_finite() && lat.abs() <= 90.0 && lon.abs() <= 180.0 {
+            Some((lat, lon))
+        } else {
+            None
+        }
+    }
+}
+
+//Every place photos were taken in, as a tree
+#[server(GetPlaces, "/api")]
+pub async fn get_places() -> Result<Vec<PlaceName>, ServerFnError> {
+    auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let rows: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT DISTINCT country, region, city FROM files
+        WHERE country IS NOT NULL ORDER BY country, region, city",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(place_tree(rows))
+}
+
+//Clustered GeoJSON of the photos matching a filter, for a map at the given zoom level
+#[server(MapClusters, "/api")]
+pub async fn map_clusters(
+    #[server(default)] filter: Filters,
+    zoom: u8,
+) -> Result<String, ServerFnError> {
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let sort = image_filter::feed_order(&filter);
+    let (mut conditions, joins, mut binds) = image_filter::prepare_filtered_query(&filter).await?;
+    conditions.push("f.latitude IS NOT NULL AND f.longitude IS NOT NULL".to_string());
+
+    let (access, access_binds) = image_filter::access_condition(user.id);
+    conditions.push(access);
+    binds.extend(access_binds);
+    binds.extend(sort.binds.iter().cloned());
+
+    let query = image_filter::build_filtered_query(
+        "SELECT DISTINCT f.id, f.latitude AS lat, f.longitude AS lon FROM files f".to_string(),
+        conditions,
+        joins,
+        Some(sort.order_by()),
+        None,
+        None,
+    );
+
+    let mut query = sqlx::query_as::<_, GeoPoint>(&query);
+    for bind in binds {
+        query = query.bind(bind);
+    }
+    let points = query.fetch_all(&pool).await?;
+
+    Ok(to_geojson(&cluster(&points, zoom)).to_string())
+}
+
+#[wasm_bindgen(inline_js = "
+const maps = {};
+
+export function init_map(id, on_select, on_zoom) {
+    const map = L.map(id).setView([20, 0], 2);
+    L.tileLayer('https://tile.openstreetmap.org/{z}/{x}/{y}.png', {
+        maxZoom: 19,
+        attribution: '&copy; OpenStreetMap contributors',
+    }).addTo(map);
+    const layer = L.geoJSON(null, {
+        pointToLayer: (feature, latlng) => L.marker(latlng, {
+            icon: L.divIcon({className: 'map-cluster', html: String(feature.properties.count)}),
+        }),
+        onEachFeature: (feature, marker) => marker.on('click', () => {
+            if (feature.properties.count == 1) {
+                on_select(feature.properties.photo);
+            } else {
+                map.setView(marker.getLatLng(), map.getZoom() + 2);
+            }
+        }),
+    }).addTo(map);
+    map.on('zoomend', () => on_zoom(map.getZoom()));
+    maps[id] = {map, layer};
+}
+
+export function set_map_data(id, geojson) {
+    const m = maps[id];
+    if (m) {
+        m.layer.clearLayers();
+        m.layer.addData(JSON.parse(geojson));
+    }
+}
+
+export function map_bounds(id) {
+    const m = maps[id];
+    if (!m) {
+        return [];
+    }
+    const b = m.map.getBounds();
+    return [b.getSouth(), b.getWest(), b.getNorth(), b.getEast()];
+}
+
+export function remove_map(id) {
+    if (maps[id]) {
+        maps[id].map.remove();
+        delete maps[id];
+    }
+}
+")]
+extern "C" {
+    fn init_map(id: &str, on_select: &Closure<dyn Fn(String)>, on_zoom: &Closure<dyn Fn(u8)>);
+    fn set_map_data(id: &str, geojson: &str);
+    fn map_bounds(id: &str) -> Vec<f64>;
+    fn remove_map(id: &str);
+}
+
+const MAP_ID: &str = "photo-map";
+
+//Map of the photos matching the filter. Single photos open on click, clusters zoom in.
+#[component]
+pub fn PhotoMap(
+    filter: ReadSignal<Filters>,
+    #[prop(into)] on_image_click: Callback<String>,
+    /// Called with the visible area when the user wants to see those photos
+    #[prop(into)]
+    on_area: Callback<GeoFilter>,
+) -> impl IntoView {
+    let (zoom, set_zoom) = create_signal(2u8);
+    let (ready, set_ready) = create_signal(false);
+    //Leaflet calls back into these until the map is removed
+    let callbacks = store_value(None::<(Closure<dyn Fn(String)>, Closure<dyn Fn(u8)>)>);
+
+    let map_ref = create_node_ref::<Div>();
+    map_ref.on_load(move |_| {
+        request_animation_frame(move || {
+            let on_select = Closure::<dyn Fn(String)>::new(move |id| on_image_click(id));
+            let on_zoom = Closure::<dyn Fn(u8)>::new(move |zoom| set_zoom(zoom));
+            init_map(MAP_ID, &on_select, &on_zoom);
+            callbacks.set_value(Some((on_select, on_zoom)));
+            set_ready(true);
+        });
+    });
+    on_cleanup(move || remove_map(MAP_ID));
+
+    let clusters = create_local_resource(
+        move || (filter.get(), zoom.get()),
+        |(filter, zoom)| async move { map_clusters(filter, zoom).await },
+    );
+
+    create_effect(move |_| {
+        if let (true, Some(Ok(geojson))) = (ready.get(), clusters.get()) {
+            set_map_data(MAP_ID, &geojson);
+        }
+    });
+
+    let show_area = move |_| {
+        if let [south, west, north, east] = map_bounds(MAP_ID)[..] {
+            on_area(GeoFilter::BoundingBox {
+                south,
+                west,
+                north,
+                east,
+            });
+        }
+    };
+
+    view! {
+        <div class="photo-map-container">
+            <button on:click=show_area>"Show photos in this area"</button>
+            <Show when=move || matches!(clusters.get(), Some(Err(_)))>
+                <p>"Could not load the map"</p>
+            </Show>
+            <div id=MAP_ID class="photo-map" node_ref=map_ref></div>
+        </div>
+    }
+}
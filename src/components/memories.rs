@@ -0,0 +1,117 @@
+#[cfg(feature = "ssr")]
+use crate::auth;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+//Most photos shown per year
+const PHOTOS_PER_YEAR: i64 = 12;
+
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct MemoryPhoto {
+    pub id: String,
+    pub data: String,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct MemoryYear {
+    pub year: i32,
+    pub years_ago: i32,
+    pub photos: Vec<MemoryPhoto>,
+}
+
+//Photos captured on today's month and day in earlier years, newest year first
+#[server(OnThisDay, "/api")]
+pub async fn on_this_day() -> Result<Vec<MemoryYear>, ServerFnError> {
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    use std::fs;
+    let pool = pool()?;
+
+    let (access, access_binds) = image_filter::access_condition(user.id);
+    let query = format!(
+        "WITH memories AS (
+            SELECT f.id, f.path, CAST(strftime('%Y', f.createdDate) AS INTEGER) AS year,
+            ROW_NUMBER() OVER (
+                PARTITION BY strftime('%Y', f.createdDate) ORDER BY f.createdDate, f.uploadDate
+            ) AS num
+            FROM files f
+            WHERE strftime('%m-%d', f.createdDate) = strftime('%m-%d', 'now', 'localtime')
+            AND strftime('%Y', f.createdDate) < strftime('%Y', 'now', 'localtime')
+            AND {})
+        SELECT id, path, year FROM memories WHERE num <= ? ORDER BY year DESC, num;",
+        access
+    );
+
+    let mut query = sqlx::query_as::<_, (String, String, i32)>(&query);
+    for bind in access_binds {
+        query = query.bind(bind);
+    }
+    let rows = query.bind(PHOTOS_PER_YEAR).fetch_all(&pool).await?;
+
+    let this_year: i32 =
+        sqlx::query_scalar("SELECT CAST(strftime('%Y', 'now', 'localtime') AS INTEGER)")
+            .fetch_one(&pool)
+            .await?;
+
+    let mut years: Vec<MemoryYear> = Vec::new();
+    for (id, path, year) in rows {
+        //A missing file should not hide the other memories
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Could not read {}: {}", path, e);
+                continue;
+            }
+        };
+        let photo = MemoryPhoto {
+            id,
+            data: base64::encode(data),
+        };
+        match years.last_mut() {
+            Some(memory) if memory.year == year => memory.photos.push(photo),
+            _ => years.push(MemoryYear {
+                year,
+                years_ago: this_year - year,
+                photos: vec![photo],
+            }),
+        }
+    }
+
+    Ok(years)
+}
+
+//"On this day" section, hidden when there is nothing to remember
+#[component]
+pub fn OnThisDay(#[prop(into)] on_image_click: Callback<String>) -> impl IntoView {
+    let memories = create_resource(|| (), |_| async { on_this_day().await.unwrap_or_default() });
+
+    view! {
+        <Transition fallback=|| ()>
+            {move || memories.get().filter(|years| !years.is_empty()).map(|years| view! {
+                <div class="memories">
+                    <h2>"On this day"</h2>
+                    {years.into_iter().map(|memory| view! {
+                        <div class="memory-year">
+                            <h3>{match memory.years_ago {
+                                1 => "1 year ago".to_string(),
+                                n => format!("{} years ago", n),
+                            }}" ("{memory.year}")"</h3>
+                            <div class="memory-photos">
+                                {memory.photos.into_iter().map(|photo| {
+                                    let id = photo.id.clone();
+                                    view! {
+                                        <img
+                                            src=format!("data:image/jpeg;base64,{}", photo.data)
+                                            on:click=move |_| on_image_click(id.clone())/>
+                                    }
+                                }).collect_view()}
+                            </div>
+                        </div>
+                    }).collect_view()}
+                </div>
+            })}
+        </Transition>
+    }
+}
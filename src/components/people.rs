@@ -0,0 +1,610 @@
+#[cfg(feature = "ssr")]
+use crate::auth;
+use leptos::html::{Input, Select};
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+//Face crops offered as avatars
+#[cfg(feature = "ssr")]
+const AVATAR_CHOICES: i64 = 24;
+
+/// Someone who can be tagged in photos, with or without an account.
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd, Clone, Serialize, Deserialize)]
+pub struct PersonInfo {
+    pub id: i64,
+    pub name: String,
+}
+
+/// A person with the photos the user can see them in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersonProfile {
+    pub id: i64,
+    pub name: String,
+    /// Base64 WebP face crop
+    pub avatar: Option<String>,
+    pub photos: i64,
+    /// Capture dates of the first and last photo
+    pub first: Option<String>,
+    pub last: Option<String>,
+    /// Whether the user may change the avatar, as the person or an admin
+    pub editable: bool,
+}
+
+/// Tagged face of a person, offered as avatar.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersonFace {
+    pub file_id: String,
+    /// Base64 WebP crop of the face
+    pub crop: String,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::PersonProfile;
+    use crate::auth::User;
+    use crate::image_filter::image_filter;
+    use sqlx::{Sqlite, SqlitePool, Transaction};
+
+    /// Profiles of everyone, or of one person.
+    pub async fn profiles(
+        user: &User,
+        person_id: Option<i64>,
+        pool: &SqlitePool,
+    ) -> Result<Vec<PersonProfile>, sqlx::Error> {
+        let (access, access_binds) = image_filter::access_condition(user.id);
+        let query = format!(
+            "SELECT p.id, p.name, p.avatar, (COALESCE(p.userID = ?, 0) OR ?) AS editable,
+                COUNT(f.id) AS photos, MIN(f.createdDate) AS first, MAX(f.createdDate) AS last
+            FROM people p
+            LEFT JOIN userFile uf ON uf.personID = p.id
+            LEFT JOIN files f ON f.id = uf.fileID AND {}
+            WHERE ? IS NULL OR p.id = ?
+            GROUP BY p.id ORDER BY p.name",
+            access
+        );
+        let mut query = sqlx::query_as::<
+            _,
+            (
+                i64,
+                String,
+                Option<Vec<u8>>,
+                bool,
+                i64,
+                Option<String>,
+                Option<String>,
+            ),
+        >(&query)
+        .bind(user.id)
+        .bind(user.has("admin"));
+        for bind in access_binds {
+            query = query.bind(bind);
+        }
+        let rows = query
+            .bind(person_id)
+            .bind(person_id)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(id, name, avatar, editable, photos, first, last)| PersonProfile {
+                    id,
+                    name,
+                    avatar: avatar.map(base64::encode),
+                    photos,
+                    first,
+                    last,
+                    editable,
+                },
+            )
+            .collect())
+    }
+
+    /// Id of the person with the name, created when there is none. A new
+    /// person is linked to the account with the same username, if any.
+    pub async fn person_id(name: &str, pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+        let existing: Option<i64> = sqlx::query_scalar("SELECT id FROM people WHERE name = ?")
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+        if let Some(id) = existing {
+            return Ok(id);
+        }
+
+        let res = sqlx::query(
+            "INSERT INTO people (name, userID) VALUES (?, (
+                SELECT u.id FROM users u WHERE u.username = ?
+                AND NOT EXISTS (SELECT 1 FROM people p WHERE p.userID = u.id)))",
+        )
+        .bind(name)
+        .bind(name)
+        .execute(pool)
+        .await?;
+
+        Ok(res.last_insert_rowid())
+    }
+
+    /// Move all photos, the account and the avatar of `from` to `into` and
+    /// remove `from`.
+    pub async fn merge_into(
+        from: i64,
+        into: i64,
+        tx: &mut Transaction<'_, Sqlite>,
+    ) -> Result<(), sqlx::Error> {
+        // In photos with both, the marked face is kept
+        sqlx::query(
+            "UPDATE userFile AS uf
+            SET x = o.x, y = o.y, width = o.width, height = o.height, embedding = o.embedding
+            FROM userFile o
+            WHERE uf.personID = ? AND o.personID = ? AND o.fileID = uf.fileID
+            AND COALESCE(uf.width, 0) = 0 AND o.width > 0",
+        )
+        .bind(into)
+        .bind(from)
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query("UPDATE OR IGNORE userFile SET personID = ? WHERE personID = ?")
+            .bind(into)
+            .bind(from)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query("DELETE FROM userFile WHERE personID = ?")
+            .bind(from)
+            .execute(&mut **tx)
+            .await?;
+
+        let (user_id, avatar) = sqlx::query_as::<_, (Option<i64>, Option<Vec<u8>>)>(
+            "DELETE FROM people WHERE id = ? RETURNING userID, avatar",
+        )
+        .bind(from)
+        .fetch_one(&mut **tx)
+        .await?;
+        sqlx::query(
+            "UPDATE people SET userID = COALESCE(userID, ?), avatar = COALESCE(avatar, ?)
+            WHERE id = ?",
+        )
+        .bind(user_id)
+        .bind(avatar)
+        .bind(into)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[server(GetPeople, "/api")]
+pub async fn get_people() -> Result<Vec<PersonInfo>, ServerFnError> {
+    auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let people = sqlx::query_as::<_, PersonInfo>("SELECT id, name FROM people ORDER BY name")
+        .fetch_all(&pool)
+        .await?;
+
+    Ok(people)
+}
+
+#[server(PeopleProfiles, "/api")]
+pub async fn get_people_profiles() -> Result<Vec<PersonProfile>, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    Ok(ssr::profiles(&user, None, &pool).await?)
+}
+
+#[server(PersonProfileFn, "/api")]
+pub async fn get_person_profile(person_id: i64) -> Result<PersonProfile, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    ssr::profiles(&user, Some(person_id), &pool)
+        .await?
+        .pop()
+        .ok_or_else(|| ServerFnError::ServerError("Person not found".to_string()))
+}
+
+//Crops of the faces the person is tagged with, newest photos first
+#[server(PersonFaces, "/api")]
+pub async fn get_person_faces(person_id: i64) -> Result<Vec<PersonFace>, ServerFnError> {
+    use crate::components::upload::{img_from_bounds, Bbox};
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let (access, access_binds) = image_filter::access_condition(user.id);
+    let query = format!(
+        "SELECT f.id, f.path, uf.x, uf.y, uf.width, uf.height
+        FROM userFile uf INNER JOIN files f ON f.id = uf.fileID
+        WHERE uf.personID = ? AND uf.width > 0 AND uf.height > 0 AND {}
+        ORDER BY f.createdDate DESC LIMIT ?",
+        access
+    );
+    let mut query =
+        sqlx::query_as::<_, (String, String, u32, u32, u32, u32)>(&query).bind(person_id);
+    for bind in access_binds {
+        query = query.bind(bind);
+    }
+    let faces = query.bind(AVATAR_CHOICES).fetch_all(&pool).await?;
+
+    let faces = tokio::task::spawn_blocking(move || {
+        faces
+            .into_iter()
+            .filter_map(|(file_id, path, x, y, w, h)| {
+                let image = image::open(path).ok()?;
+                Some(PersonFace {
+                    file_id,
+                    crop: img_from_bounds(&image, Some(Bbox { x, y, w, h })),
+                })
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+
+    Ok(faces)
+}
+
+//Use the face of the person in a photo as their avatar
+#[server(SetAvatar, "/api")]
+pub async fn set_avatar(person_id: i64, file_id: String) -> Result<(), ServerFnError> {
+    use crate::components::upload::{img_from_bounds, Bbox};
+    use crate::image_filter::image_filter;
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let linked: Option<Option<i64>> = sqlx::query_scalar("SELECT userID FROM people WHERE id = ?")
+        .bind(person_id)
+        .fetch_optional(&pool)
+        .await?;
+    let Some(linked) = linked else {
+        return Err(ServerFnError::ServerError("Person not found".to_string()));
+    };
+    if linked != Some(user.id) && !user.has("admin") {
+        return Err(ServerFnError::ServerError(
+            "Only the person or an admin can change the avatar".to_string(),
+        ));
+    }
+
+    if !image_filter::can_view_file(&file_id, user.id, &pool).await? {
+        return Err(ServerFnError::ServerError("Photo not found".to_string()));
+    }
+    let face = sqlx::query_as::<_, (String, u32, u32, u32, u32)>(
+        "SELECT f.path, uf.x, uf.y, uf.width, uf.height
+        FROM userFile uf INNER JOIN files f ON f.id = uf.fileID
+        WHERE uf.personID = ? AND uf.fileID = ? AND uf.width > 0 AND uf.height > 0",
+    )
+    .bind(person_id)
+    .bind(&file_id)
+    .fetch_optional(&pool)
+    .await?;
+    let Some((path, x, y, w, h)) = face else {
+        return Err(ServerFnError::ServerError(
+            "The person is not marked in this photo".to_string(),
+        ));
+    };
+
+    let avatar = tokio::task::spawn_blocking(move || {
+        let image = image::open(path)?;
+        Ok::<_, image::ImageError>(img_from_bounds(&image, Some(Bbox { x, y, w, h })))
+    })
+    .await??;
+
+    sqlx::query("UPDATE people SET avatar = ? WHERE id = ?")
+        .bind(base64::decode(avatar)?)
+        .bind(person_id)
+        .execute(&pool)
+        .await?;
+
+    Ok(())
+}
+
+//Change the name of a person, also in the search index
+#[server(RenamePerson, "/api")]
+pub async fn rename_person(person_id: i64, name: String) -> Result<(), ServerFnError> {
+    auth::authorized("admin").await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ServerFnError::ServerError("A name is required".to_string()));
+    }
+
+    let taken: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM people WHERE name = ? AND id != ?)")
+            .bind(name)
+            .bind(person_id)
+            .fetch_one(&pool)
+            .await?;
+    if taken {
+        return Err(ServerFnError::ServerError(format!(
+            "{} already exists, merge the people instead",
+            name
+        )));
+    }
+
+    let res = sqlx::query("UPDATE people SET name = ? WHERE id = ?")
+        .bind(name)
+        .bind(person_id)
+        .execute(&pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ServerFnError::ServerError("Person not found".to_string()));
+    }
+
+    Ok(())
+}
+
+//Move the photos of a duplicate person to another and remove the duplicate
+#[server(MergePeople, "/api")]
+pub async fn merge_people(from: i64, into: i64) -> Result<(), ServerFnError> {
+    auth::authorized("admin").await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    if from == into {
+        return Err(ServerFnError::ServerError(
+            "A person can not be merged into themselves".to_string(),
+        ));
+    }
+
+    let people =
+        sqlx::query_as::<_, (i64, Option<i64>)>("SELECT id, userID FROM people WHERE id IN (?, ?)")
+            .bind(from)
+            .bind(into)
+            .fetch_all(&pool)
+            .await?;
+    if people.len() != 2 {
+        return Err(ServerFnError::ServerError("Person not found".to_string()));
+    }
+    if people.iter().all(|(_, user_id)| user_id.is_some()) {
+        return Err(ServerFnError::ServerError(
+            "Both people have an account and can not be merged".to_string(),
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+    ssr::merge_into(from, into, &mut tx).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+//Rename and merge duplicate people
+#[component]
+pub fn PeoplePanel() -> impl IntoView {
+    let people = create_resource(|| (), |_| async { get_people_profiles().await });
+    let (message, set_message) = create_signal(None::<String>);
+
+    let report = move |res: Result<(), ServerFnError>| {
+        match res {
+            Ok(_) => set_message(None),
+            Err(e) => set_message(Some(e.to_string())),
+        }
+        people.refetch();
+    };
+
+    view! {
+        <h2>"People"</h2>
+        <Show when=move || message().is_some()>
+            <p>{message().unwrap()}</p>
+        </Show>
+        <Suspense fallback=move || view! {<p>"Loading people"</p>}>
+            <ErrorBoundary fallback=|_| {view! {<p>"Something went wrong"</p>}}>
+                {move || people.get().map(|res| res.map(|list| {
+                    let everyone = store_value(list.clone());
+                    view! {
+                        <div class="userlist">
+                        {list.into_iter().map(|person| {
+                            let name_ref = create_node_ref::<Input>();
+                            let merge_ref = create_node_ref::<Select>();
+                            let (merge_prompt, set_merge_prompt) = create_signal(None::<i64>);
+                            let id = person.id;
+                            let name = person.name.clone();
+                            //Merging can not be undone
+                            let prompt = move || merge_prompt().map(|into| {
+                                let other = everyone.with_value(|everyone| {
+                                    everyone.iter().find(|other| other.id == into).map(|other| other.name.clone())
+                                });
+                                format!("Merge {} into {}", name, other.unwrap_or_default())
+                            });
+                            view! {
+                                <div class="user-item">
+                                    <p>{person.name}" ("{person.photos}")"</p>
+                                    <div class="buttons">
+                                        <input type="text" placeholder="New name" _ref=name_ref/>
+                                        <button on:click=move |_| {
+                                            let name = name_ref.get_untracked().unwrap().value();
+                                            spawn_local(async move {
+                                                report(rename_person(id, name).await);
+                                            })
+                                        }>"Rename"</button>
+                                        <select _ref=merge_ref>
+                                            {everyone.get_value().into_iter()
+                                                .filter(|other| other.id != id)
+                                                .map(|other| view! {
+                                                    <option value={other.id.to_string()}>{other.name}</option>
+                                                }).collect_view()}
+                                        </select>
+                                        <Show
+                                            when=move || merge_prompt().is_some()
+                                            fallback=move || view! {
+                                                <button on:click=move |_| {
+                                                    if let Ok(into) = merge_ref.get_untracked().unwrap().value().parse::<i64>() {
+                                                        set_merge_prompt(Some(into));
+                                                    }
+                                                }>"Merge into"</button>
+                                            }>
+                                            <button
+                                                style="background-color: red;"
+                                                on:click=move |_| {
+                                                    if let Some(into) = merge_prompt.get_untracked() {
+                                                        set_merge_prompt(None);
+                                                        spawn_local(async move {
+                                                            report(merge_people(id, into).await);
+                                                        })
+                                                    }
+                                                }>{prompt.clone()}</button>
+                                            <button style="background-color: gray;" on:click=move |_| set_merge_prompt(None)>"Cancel"</button>
+                                        </Show>
+                                    </div>
+                                </div>
+                            }
+                        }).collect_view()}
+                        </div>
+                    }
+                }))}
+            </ErrorBoundary>
+        </Suspense>
+    }
+}
+
+//Avatar, or the initial of the name when there is none
+#[component]
+fn Avatar(#[prop(into)] name: String, avatar: Option<String>) -> impl IntoView {
+    match avatar {
+        Some(avatar) => view! {
+            <img class="avatar" src=format!("data:image/webp;base64,{}", avatar)/>
+        }
+        .into_view(),
+        None => view! {
+            <span class="avatar">{name.chars().next().unwrap_or_default().to_string()}</span>
+        }
+        .into_view(),
+    }
+}
+
+fn date_range(person: &PersonProfile) -> Option<String> {
+    let day = |date: &String| date.chars().take(10).collect::<String>();
+    match (&person.first, &person.last) {
+        (Some(first), Some(last)) if day(first) != day(last) => {
+            Some(format!("{} – {}", day(first), day(last)))
+        }
+        (Some(first), _) => Some(day(first)),
+        _ => None,
+    }
+}
+
+//Everyone tagged in photos
+#[component]
+pub fn PeoplePage() -> impl IntoView {
+    let people = create_resource(|| (), |_| async { get_people_profiles().await });
+
+    view! {
+        <h1>"People"</h1>
+        <Transition fallback=move || view! {<p>"Loading"</p>}>
+            <ErrorBoundary fallback=|_| {view! {<p>"Something went wrong"</p>}}>
+                {move || people.get().map(|res| res.map(|list| {
+                    view! {
+                        <div class="people">
+                            {list.into_iter().map(|person| view! {
+                                <a class="person-card" href=format!("/people/{}", person.id)>
+                                    <Avatar name=person.name.clone() avatar=person.avatar/>
+                                    <span class="name">{person.name}</span>
+                                    <span class="photos">{format!("{} photos", person.photos)}</span>
+                                </a>
+                            }).collect_view()}
+                        </div>
+                    }
+                }))}
+            </ErrorBoundary>
+        </Transition>
+    }
+}
+
+//Photo count and dates of a person, with an avatar picker for the person and admins
+#[component]
+pub fn PersonPage() -> impl IntoView {
+    let params = use_params_map();
+    let person_id = move || {
+        params.with(|p| {
+            p.get("id")
+                .and_then(|id| id.parse::<i64>().ok())
+                .unwrap_or_default()
+        })
+    };
+
+    let person = create_resource(person_id, |id| async move { get_person_profile(id).await });
+    let (choosing, set_choosing) = create_signal(false);
+    let faces = create_resource(
+        move || (person_id(), choosing.get()),
+        |(id, choosing)| async move {
+            if choosing {
+                get_person_faces(id).await.unwrap_or_default()
+            } else {
+                vec![]
+            }
+        },
+    );
+    let (error, set_error) = create_signal(None::<String>);
+
+    let choose = move |file_id: String| {
+        spawn_local(async move {
+            match set_avatar(person_id(), file_id).await {
+                Ok(_) => {
+                    set_error(None);
+                    set_choosing(false);
+                    person.refetch();
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+
+    view! {
+        <Transition fallback=move || view! {<p>"Loading"</p>}>
+            <ErrorBoundary fallback=|_| {view! {<p>"Person not found"</p>}}>
+                {move || person.get().map(|res| res.map(|profile| {
+                    let range = date_range(&profile);
+                    let editable = profile.editable;
+                    view! {
+                        <div class="person-profile">
+                            <Avatar name=profile.name.clone() avatar=profile.avatar/>
+                            <div>
+                                <h1>{profile.name}</h1>
+                                <p>{format!("{} photos", profile.photos)}</p>
+                                {range.map(|range| view! {<p>{range}</p>})}
+                                <a href=format!("/?person={}", profile.id)>"Show photos"</a>
+                                <Show when=move || editable>
+                                    <button on:click=move |_| set_choosing.update(|c| *c = !*c)>
+                                        {move || if choosing() { "Cancel" } else { "Choose avatar" }}
+                                    </button>
+                                </Show>
+                            </div>
+                        </div>
+                    }
+                }))}
+            </ErrorBoundary>
+        </Transition>
+        <Show when=choosing>
+            <Transition fallback=move || view! {<p>"Finding faces"</p>}>
+                <div class="avatar-choices">
+                    {move || faces.get().map(|faces| {
+                        if faces.is_empty() {
+                            return view! {<p>"No marked faces"</p>}.into_view();
+                        }
+                        faces.into_iter().map(|face| {
+                            let file_id = face.file_id.clone();
+                            view! {
+                                <img
+                                    src=format!("data:image/webp;base64,{}", face.crop)
+                                    on:click=move |_| choose(file_id.clone())/>
+                            }
+                        }).collect_view()
+                    })}
+                </div>
+            </Transition>
+        </Show>
+        <Show when=move || error().is_some()>
+            <p>{move || error().unwrap_or_default()}</p>
+        </Show>
+    }
+}
@@ -0,0 +1,188 @@
+#[cfg(feature = "ssr")]
+use crate::auth;
+use crate::auth::User;
+use crate::components::home_page::Filters;
+use leptos::html::Input;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+//A named filter, shown as a smart album whose contents are computed on every visit
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedFilter {
+    pub id: i64,
+    pub name: String,
+    pub global: bool,
+    pub filter: Filters,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(sqlx::FromRow)]
+struct SavedFilterRow {
+    id: i64,
+    name: String,
+    global: bool,
+    filter: String,
+}
+
+//Filters saved by the user and those shared with everyone
+#[server(GetSavedFilters, "/api")]
+pub async fn get_saved_filters() -> Result<Vec<SavedFilter>, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let rows = sqlx::query_as::<_, SavedFilterRow>(
+        "SELECT id, name, userID IS NULL AS global, filter FROM savedFilters
+        WHERE userID IS NULL OR userID = ?
+        ORDER BY global DESC, name;",
+    )
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| -> Result<SavedFilter, ServerFnError> {
+            Ok(SavedFilter {
+                id: row.id,
+                name: row.name,
+                global: row.global,
+                filter: serde_json::from_str(&row.filter)?,
+            })
+        })
+        .collect()
+}
+
+#[server(SaveFilter, "/api")]
+pub async fn save_filter(
+    name: String,
+    filter: Filters,
+    global: bool,
+) -> Result<i64, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ServerFnError::ServerError(
+            "The filter needs a name".to_string(),
+        ));
+    }
+
+    // Only admins can share filters with everyone
+    if global && !user.has("admin") {
+        return Err(ServerFnError::ServerError(
+            "You are not authorized".to_string(),
+        ));
+    }
+
+    let res = sqlx::query(
+        "INSERT INTO savedFilters (name, userID, filter, createdDate)
+        VALUES (?, ?, ?, datetime('now', 'localtime'))",
+    )
+    .bind(name)
+    .bind(if global { None } else { Some(user.id) })
+    .bind(serde_json::to_string(&filter)?)
+    .execute(&pool)
+    .await?;
+
+    Ok(res.last_insert_rowid())
+}
+
+#[server(DeleteSavedFilter, "/api")]
+pub async fn delete_saved_filter(id: i64) -> Result<(), ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    sqlx::query("DELETE FROM savedFilters WHERE id = ? AND (userID = ? OR (userID IS NULL AND ?))")
+        .bind(id)
+        .bind(user.id)
+        .bind(user.has("admin"))
+        .execute(&pool)
+        .await?;
+
+    Ok(())
+}
+
+//Sidebar listing the saved filters
+#[component]
+pub fn SavedFilterList(
+    filter: ReadSignal<Filters>,
+    #[prop(into)] on_select: Callback<Filters>,
+) -> impl IntoView {
+    let saved = create_resource(
+        || (),
+        |_| async { get_saved_filters().await.unwrap_or_default() },
+    );
+    let (error, set_error) = create_signal(None::<String>);
+
+    let name_input = create_node_ref::<Input>();
+    let global_input = create_node_ref::<Input>();
+
+    let is_admin = move || {
+        use_context::<User>()
+            .map(|user| user.has("admin"))
+            .unwrap_or(false)
+    };
+
+    let save = move |_| {
+        let name = name_input.get_untracked().unwrap().value();
+        let global = global_input
+            .get_untracked()
+            .map(|input| input.checked())
+            .unwrap_or(false);
+        spawn_local(async move {
+            match save_filter(name, filter.get_untracked(), global).await {
+                Ok(_) => {
+                    set_error(None);
+                    name_input.get_untracked().unwrap().set_value("");
+                    saved.refetch();
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+
+    let remove = move |id: i64| {
+        spawn_local(async move {
+            if delete_saved_filter(id).await.is_ok() {
+                saved.refetch();
+            }
+        });
+    };
+
+    view! {
+        <aside class="sidebar">
+            <h3>"Smart albums"</h3>
+            <a class="saved-filter"
+                class:active=move || filter.get() == Filters::default()
+                on:click=move |_| on_select(Filters::default())>"All photos"</a>
+            {move || saved.get().unwrap_or_default().into_iter().map(|saved_filter| {
+                let id = saved_filter.id;
+                let deletable = !saved_filter.global || is_admin();
+                let active_filter = saved_filter.filter.clone();
+                let select_filter = saved_filter.filter.clone();
+                view! {
+                    <div class="saved-filter horizontal">
+                        <a class:active=move || filter.get() == active_filter
+                            on:click=move |_| on_select(select_filter.clone())>
+                            {saved_filter.name}
+                        </a>
+                        <Show when=move || deletable>
+                            <button on:click=move |_| remove(id)>"x"</button>
+                        </Show>
+                    </div>
+                }
+            }).collect_view()}
+            <input type="text" placeholder="Name of current filter" _ref=name_input/>
+            <Show when=is_admin>
+                <label><input type="checkbox" _ref=global_input/>"Share with everyone"</label>
+            </Show>
+            <button on:click=save>"Save filter"</button>
+            <Show when=move || error().is_some()>
+                <span>{error().unwrap()}</span>
+            </Show>
+        </aside>
+    }
+}
@@ -0,0 +1,510 @@
+#[cfg(feature = "ssr")]
+use crate::auth;
+use leptos::html::Input;
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub token: String,
+    pub target: String,
+    pub expires_at: Option<String>,
+    pub protected: bool,
+    pub allow_download: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SharedImage {
+    pub data: String,
+    pub file_name: String,
+}
+
+//Everything an anonymous visitor of a share link gets to see
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SharedPage {
+    pub title: String,
+    pub password_required: bool,
+    pub allow_download: bool,
+    /// Base64 WebP cover of a shared album
+    pub cover: Option<String>,
+    pub images: Vec<SharedImage>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(sqlx::FromRow)]
+struct Share {
+    file_id: Option<String>,
+    album_id: Option<String>,
+    password: Option<String>,
+    allow_download: bool,
+    title: String,
+    created_by: i64,
+}
+
+//Images per page in the shared view
+const SHARE_PAGE_SIZE: usize = 20;
+//Longest side of the photos sent for links that do not allow downloads
+#[cfg(feature = "ssr")]
+const PREVIEW_SIZE: u32 = 1280;
+
+//Smaller JPEG of a photo, so the original can not be saved from the page
+#[cfg(feature = "ssr")]
+fn preview(image: &[u8]) -> Result<Vec<u8>, image::ImageError> {
+    let image = image::load_from_memory(image)?.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE);
+    let mut buf = Vec::new();
+    image::DynamicImage::ImageRgb8(image.to_rgb8()).write_to(
+        &mut std::io::Cursor::new(&mut buf),
+        image::ImageFormat::Jpeg,
+    )?;
+    Ok(buf)
+}
+
+#[server(CreateShareLink, "/api")]
+pub async fn create_share_link(
+    file_id: Option<String>,
+    album_id: Option<String>,
+    expires_in_days: Option<u32>,
+    password: Option<String>,
+    allow_download: bool,
+) -> Result<String, ServerFnError> {
+    use crate::components::albums::{ssr::album_role, ROLE_OWNER};
+    use crate::db::ssr::pool;
+    use crate::image_filter::image_filter;
+    use bcrypt::{hash, DEFAULT_COST};
+    use uuid::Uuid;
+
+    let user = auth::logged_in().await?;
+    let pool = pool()?;
+
+    // Only uploaders and admins may share a photo, only owners (or admins for public albums)
+    // may share an album
+    let allowed = match (&file_id, &album_id) {
+        (Some(file_id), None) => {
+            let uploader: bool = sqlx::query_scalar("SELECT uploadedBy=? FROM files WHERE id = ?")
+                .bind(user.id)
+                .bind(file_id)
+                .fetch_one(&pool)
+                .await?;
+
+            image_filter::can_view_file(file_id, user.id, &pool).await?
+                && (uploader || user.has("admin"))
+        }
+        (None, Some(album_id)) => {
+            let private: bool = sqlx::query_scalar("SELECT private FROM albums WHERE id = ?")
+                .bind(album_id)
+                .fetch_one(&pool)
+                .await?;
+
+            match album_role(album_id, user.id, &pool).await? {
+                Some(role) => role == ROLE_OWNER || (user.has("admin") && !private),
+                None => false,
+            }
+        }
+        _ => {
+            return Err(ServerFnError::ServerError(
+                "A share link needs either a photo or an album".to_string(),
+            ))
+        }
+    };
+
+    if !allowed {
+        return Err(ServerFnError::ServerError(
+            "You are not authorized to share this".to_string(),
+        ));
+    }
+
+    let password = match password.filter(|p| !p.is_empty()) {
+        Some(p) => Some(hash(p, DEFAULT_COST)?),
+        None => None,
+    };
+
+    let token = Uuid::new_v4().to_string();
+
+    // datetime() returns NULL when the modifier is NULL, which means the link never expires
+    sqlx::query(
+        "INSERT INTO shares (token, fileID, albumID, created_by, expires_at, password, allow_download)
+        VALUES (?, ?, ?, ?, datetime('now', ?), ?, ?)",
+    )
+    .bind(&token)
+    .bind(file_id)
+    .bind(album_id)
+    .bind(user.id)
+    .bind(expires_in_days.map(|days| format!("+{} days", days)))
+    .bind(password)
+    .bind(allow_download)
+    .execute(&pool)
+    .await?;
+
+    Ok("/share/".to_string() + &token)
+}
+
+//Share links for a photo or an album. Without a target, all links the user may manage
+#[server(GetShareLinks, "/api")]
+pub async fn get_share_links(
+    file_id: Option<String>,
+    album_id: Option<String>,
+) -> Result<Vec<ShareLink>, ServerFnError> {
+    use crate::db::ssr::pool;
+    let user = auth::logged_in().await?;
+    let pool = pool()?;
+
+    let links = sqlx::query_as::<_, ShareLink>(
+        "SELECT s.token, COALESCE(a.name, 'Photo') AS target, s.expires_at,
+            s.password IS NOT NULL AS protected, s.allow_download
+        FROM shares s
+        LEFT JOIN albums a ON a.id = s.albumID
+        WHERE (s.created_by = ? OR ?)
+            AND (? IS NULL OR s.fileID = ?)
+            AND (? IS NULL OR s.albumID = ?)
+        ORDER BY s.created_at DESC;",
+    )
+    .bind(user.id)
+    .bind(user.has("admin"))
+    .bind(&file_id)
+    .bind(&file_id)
+    .bind(&album_id)
+    .bind(&album_id)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(links)
+}
+
+#[server(RevokeShareLink, "/api")]
+pub async fn revoke_share_link(token: String) -> Result<(), ServerFnError> {
+    use crate::db::ssr::pool;
+    let user = auth::logged_in().await?;
+    let pool = pool()?;
+
+    sqlx::query("DELETE FROM shares WHERE token = ? AND (created_by = ? OR ?)")
+        .bind(token)
+        .bind(user.id)
+        .bind(user.has("admin"))
+        .execute(&pool)
+        .await?;
+
+    Ok(())
+}
+
+//Anonymous access to shared content. Requires no login, only a valid token.
+#[server(GetShared, "/api")]
+pub async fn get_shared(
+    token: String,
+    password: Option<String>,
+    db_index: usize,
+    count: usize,
+) -> Result<SharedPage, ServerFnError> {
+    use crate::db::ssr::pool;
+    use crate::image_filter::image_filter;
+    use std::fs::File;
+    use std::io::Read;
+    let pool = pool()?;
+
+    let share = sqlx::query_as::<_, Share>(
+        "SELECT s.fileID AS file_id, s.albumID AS album_id, s.password, s.allow_download,
+            COALESCE(a.name, 'Shared photo') AS title, s.created_by
+        FROM shares s
+        LEFT JOIN albums a ON a.id = s.albumID
+        WHERE s.token = ? AND (s.expires_at IS NULL OR s.expires_at > datetime('now'));",
+    )
+    .bind(token)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| {
+        ServerFnError::ServerError("This link does not exist or has expired".to_string())
+    })?;
+
+    if let Some(hashed) = share.password {
+        let Some(password) = password else {
+            // Nothing about the content is shown before the password
+            return Ok(SharedPage {
+                title: "Password protected".to_string(),
+                password_required: true,
+                allow_download: false,
+                cover: None,
+                images: Vec::new(),
+            });
+        };
+
+        // Verifying the password is blocking and potentially slow
+        let valid =
+            tokio::task::spawn_blocking(move || bcrypt::verify(password, &hashed)).await??;
+        if !valid {
+            return Err(ServerFnError::ServerError("Wrong password".to_string()));
+        }
+    }
+
+    // Only what the creator of the link can see is shared, photos can also
+    // be in private albums of others
+    let cover = match &share.album_id {
+        Some(album_id) if db_index == 0 => {
+            crate::components::albums::ssr::cover(album_id, share.created_by, &pool).await?
+        }
+        _ => None,
+    };
+    let (access, access_binds) = image_filter::access_condition(share.created_by);
+    let count = count.min(SHARE_PAGE_SIZE);
+    let paths: Vec<String> = match (share.file_id, share.album_id) {
+        (Some(file_id), _) if db_index == 0 => {
+            let query = format!("SELECT f.path FROM files f WHERE f.id = ? AND {}", access);
+            let mut query = sqlx::query_scalar(&query).bind(file_id);
+            for bind in access_binds {
+                query = query.bind(bind);
+            }
+            query.fetch_all(&pool).await?
+        }
+        (_, Some(album_id)) => {
+            let query = format!(
+                "SELECT f.path FROM albumFile af
+                INNER JOIN files f ON f.id = af.fileID
+                WHERE af.albumID = ? AND {}
+                ORDER BY af.position, f.uploadDate DESC LIMIT ? OFFSET ?;",
+                access
+            );
+            let mut query = sqlx::query_scalar(&query).bind(album_id);
+            for bind in access_binds {
+                query = query.bind(bind);
+            }
+            query
+                .bind(count as i64)
+                .bind(db_index as i64)
+                .fetch_all(&pool)
+                .await?
+        }
+        _ => Vec::new(),
+    };
+
+    let mut images = Vec::new();
+    for (i, path) in paths.into_iter().enumerate() {
+        // Read the image file
+        let mut file = File::open(&path)?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
+        if !share.allow_download {
+            buffer = tokio::task::spawn_blocking(move || preview(&buffer)).await??;
+        }
+
+        let ext = path.rsplit('.').next().unwrap_or("jpg");
+        images.push(SharedImage {
+            data: base64::encode(&buffer),
+            file_name: format!("photo-{}.{}", db_index + i + 1, ext),
+        });
+    }
+
+    Ok(SharedPage {
+        title: share.title,
+        password_required: false,
+        allow_download: share.allow_download,
+        cover,
+        images,
+    })
+}
+
+//Create and list share links for a photo or an album
+#[component]
+pub fn ShareLinks(file_id: Option<String>, album_id: Option<String>) -> impl IntoView {
+    let target = store_value((file_id, album_id));
+    let links = create_resource(
+        move || target.get_value(),
+        |(file_id, album_id)| async move {
+            get_share_links(file_id, album_id).await.unwrap_or_default()
+        },
+    );
+
+    let expiry_input = create_node_ref::<Input>();
+    let password_input = create_node_ref::<Input>();
+    let download_input = create_node_ref::<Input>();
+    let link_output = create_node_ref::<Input>();
+    let (error, set_error) = create_signal(None::<String>);
+
+    let create = move |_| {
+        let (file_id, album_id) = target.get_value();
+        let expires_in_days = expiry_input
+            .get_untracked()
+            .unwrap()
+            .value()
+            .parse::<u32>()
+            .ok();
+        let password = password_input.get_untracked().unwrap().value();
+        let allow_download = download_input.get_untracked().unwrap().checked();
+        spawn_local(async move {
+            match create_share_link(
+                file_id,
+                album_id,
+                expires_in_days,
+                Some(password),
+                allow_download,
+            )
+            .await
+            {
+                Ok(link) => {
+                    set_error(None);
+                    let origin = window().location().origin().unwrap_or_default();
+                    link_output
+                        .get_untracked()
+                        .unwrap()
+                        .set_value(&(origin + &link));
+                    links.refetch();
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        });
+    };
+
+    let revoke = move |token: String| {
+        spawn_local(async move {
+            if revoke_share_link(token).await.is_ok() {
+                links.refetch();
+            }
+        });
+    };
+
+    view! {
+        <div class="share-links">
+            <h3>"Share with people outside the album"</h3>
+            <label>"Expires after (days, empty for never)"
+                <input type="number" min="1" _ref=expiry_input/>
+            </label>
+            <label>"Password (optional)"
+                <input type="password" _ref=password_input/>
+            </label>
+            <label><input type="checkbox" _ref=download_input/>"Allow downloading the originals"</label>
+            <button on:click=create>"Create link"</button>
+            <input type="text" placeholder="Share link" readonly _ref=link_output/>
+            <Show when=move || error().is_some()>
+                <span>{error().unwrap()}</span>
+            </Show>
+            {move || links.get().unwrap_or_default().into_iter().map(|link| {
+                let token = link.token.clone();
+                view! {
+                    <div class="user-item">
+                        <p>
+                            {format!("/share/{}", link.token)}
+                            {link.expires_at.map(|date| format!(" - expires {}", date))}
+                            {if link.protected {" - password"} else {""}}
+                            {if link.allow_download {" - download"} else {""}}
+                        </p>
+                        <button on:click=move |_| revoke(token.clone())>"Revoke"</button>
+                    </div>
+                }
+            }).collect_view()}
+        </div>
+    }
+}
+
+//Every share link in the album, for admins
+#[component]
+pub fn SharePanel() -> impl IntoView {
+    let links = create_resource(
+        || (),
+        |_| async { get_share_links(None, None).await.unwrap_or_default() },
+    );
+
+    let revoke = move |token: String| {
+        spawn_local(async move {
+            if revoke_share_link(token).await.is_ok() {
+                links.refetch();
+            }
+        });
+    };
+
+    view! {
+        <h2>"Share links"</h2>
+        <div class="userlist">
+            {move || links.get().unwrap_or_default().into_iter().map(|link| {
+                let token = link.token.clone();
+                view! {
+                    <div class="user-item">
+                        <p>{link.target}" - "{format!("/share/{}", link.token)}
+                            {link.expires_at.map(|date| format!(" - expires {}", date))}
+                        </p>
+                        <button on:click=move |_| revoke(token.clone())>"Revoke"</button>
+                    </div>
+                }
+            }).collect_view()}
+        </div>
+    }
+}
+
+//Read-only view of a shared photo or album, for visitors without an account
+#[component]
+pub fn SharedView() -> impl IntoView {
+    let params = use_params_map();
+    let token = move || params.with(|p| p.get("token").cloned().unwrap_or_default());
+
+    let (password, set_password) = create_signal(None::<String>);
+    let (db_index, set_db_index) = create_signal(0);
+    let (page, set_page) = create_signal(None::<SharedPage>);
+    let (images, set_images) = create_signal(Vec::<SharedImage>::new());
+    //Only sent with the first page
+    let (cover, set_cover) = create_signal(None::<String>);
+    let (has_more, set_has_more) = create_signal(false);
+    let (error, set_error) = create_signal(None::<String>);
+
+    let _loader = create_local_resource(
+        move || (password.get(), db_index.get()),
+        move |(password, db_index)| async move {
+            match get_shared(token(), password, db_index, SHARE_PAGE_SIZE).await {
+                Ok(shared) => {
+                    set_error(None);
+                    set_has_more(shared.images.len() == SHARE_PAGE_SIZE);
+                    set_images.update(|imgs| imgs.extend(shared.images.clone()));
+                    if db_index == 0 {
+                        set_cover(shared.cover.clone());
+                    }
+                    set_page(Some(shared));
+                }
+                Err(e) => set_error(Some(e.to_string())),
+            }
+        },
+    );
+
+    let password_input = create_node_ref::<Input>();
+    let unlock = move |_| {
+        let value = password_input.get_untracked().unwrap().value();
+        set_images(Vec::new());
+        set_db_index(0);
+        set_password(Some(value));
+    };
+
+    view! {
+        <div class="shared-view">
+            <Show when=move || error().is_some()>
+                <p>{error().unwrap()}</p>
+            </Show>
+            {move || match page.get() {
+                Some(shared) if shared.password_required => view! {
+                    <h1>{shared.title}</h1>
+                    <p>"This link is password protected"</p>
+                    <input type="password" placeholder="Password" _ref=password_input/>
+                    <button on:click=unlock>"Open"</button>
+                }.into_view(),
+                Some(shared) => {
+                    let allow_download = shared.allow_download;
+                    view! {
+                        {move || cover().map(|data| view! {
+                            <img class="album-cover" src=format!("data:image/webp;base64,{}", data)/>
+                        })}
+                        <h1>{shared.title}</h1>
+                        <div class="shared-images">
+                            <For each=move || images.get() key=|img| img.file_name.clone() let:img>
+                                <div class="image">
+                                    <img src={format!("data:image/jpeg;base64,{}", img.data)} alt="Shared image" class="image imageSmooth"/>
+                                    <Show when=move || allow_download>
+                                        <a href={format!("data:image/jpeg;base64,{}", img.data)} download={img.file_name.clone()}>"Download"</a>
+                                    </Show>
+                                </div>
+                            </For>
+                        </div>
+                        <Show when=has_more>
+                            <button on:click=move |_| set_db_index.update(|i| *i += SHARE_PAGE_SIZE)>"Show more"</button>
+                        </Show>
+                    }.into_view()
+                }
+                None => ().into_view(),
+            }}
+        </div>
+    }
+}
@@ -0,0 +1,182 @@
+#[cfg(feature = "ssr")]
+use crate::auth;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+//How many entries the top lists show
+#[cfg(feature = "ssr")]
+const TOP_COUNT: i64 = 10;
+
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Count {
+    pub name: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct LibraryStats {
+    pub photos: i64,
+    /// Size of all image files on disk
+    pub storage_bytes: u64,
+    pub without_capture_date: i64,
+    /// Photos nobody is tagged in
+    pub without_people: i64,
+    /// Photos with untagged faces, that is faces found by face detection
+    /// that nobody has named yet
+    pub with_unnamed_faces: i64,
+    pub per_uploader: Vec<Count>,
+    /// Uploads per `YYYY-MM`, oldest first
+    pub per_month: Vec<Count>,
+    pub top_tags: Vec<Count>,
+    pub top_people: Vec<Count>,
+}
+
+/// Human readable size, `1536` gives `1.5 KB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[server(GetLibraryStats, "/api")]
+pub async fn get_library_stats() -> Result<LibraryStats, ServerFnError> {
+    auth::authorized("admin").await?;
+    use crate::db::ssr::pool;
+    use std::fs;
+    let pool = pool()?;
+
+    let photos: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files")
+        .fetch_one(&pool)
+        .await?;
+
+    let without_capture_date: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM files WHERE createdDate IS NULL")
+            .fetch_one(&pool)
+            .await?;
+
+    let without_people: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM files f WHERE NOT EXISTS (SELECT 1 FROM userFile uf WHERE uf.fileID = f.id)",
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    let with_unnamed_faces: i64 =
+        sqlx::query_scalar("SELECT COUNT(DISTINCT fileID) FROM faces WHERE dismissed = 0")
+            .fetch_one(&pool)
+            .await?;
+
+    let per_uploader = sqlx::query_as::<_, Count>(
+        "SELECT u.username AS name, COUNT(*) AS count FROM files f
+        INNER JOIN users u ON u.id = f.uploadedBy
+        GROUP BY u.id ORDER BY count DESC, name",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let per_month = sqlx::query_as::<_, Count>(
+        "SELECT strftime('%Y-%m', uploadDate) AS name, COUNT(*) AS count FROM files
+        GROUP BY name ORDER BY name",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let top_tags = sqlx::query_as::<_, Count>(
+        "SELECT tagString AS name, COUNT(*) AS count FROM tagFile
+        GROUP BY tagString ORDER BY count DESC, name LIMIT ?",
+    )
+    .bind(TOP_COUNT)
+    .fetch_all(&pool)
+    .await?;
+
+    let top_people = sqlx::query_as::<_, Count>(
+        "SELECT p.name AS name, COUNT(DISTINCT uf.fileID) AS count FROM userFile uf
+        INNER JOIN people p ON p.id = uf.personID
+        GROUP BY p.id ORDER BY count DESC, name LIMIT ?",
+    )
+    .bind(TOP_COUNT)
+    .fetch_all(&pool)
+    .await?;
+
+    //Files missing on disk are not counted
+    let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM files")
+        .fetch_all(&pool)
+        .await?;
+    let storage_bytes = paths
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+
+    Ok(LibraryStats {
+        photos,
+        storage_bytes,
+        without_capture_date,
+        without_people,
+        with_unnamed_faces,
+        per_uploader,
+        per_month,
+        top_tags,
+        top_people,
+    })
+}
+
+#[component]
+fn CountTable(title: &'static str, counts: Vec<Count>) -> impl IntoView {
+    //Bars are relative to the largest count
+    let max = counts.iter().map(|c| c.count).max().unwrap_or(1).max(1);
+
+    view! {
+        <div class="stats-table">
+            <h3>{title}</h3>
+            <Show when={let empty = counts.is_empty(); move || empty}>
+                <p>"Nothing yet"</p>
+            </Show>
+            {counts.into_iter().map(|c| view! {
+                <div class="stats-row">
+                    <span class="stats-name">{c.name}</span>
+                    <div class="stats-bar" style=format!("width: {}%;", c.count * 100 / max)></div>
+                    <span>{c.count}</span>
+                </div>
+            }).collect_view()}
+        </div>
+    }
+}
+
+#[component]
+pub fn StatsPage() -> impl IntoView {
+    let stats = create_resource(|| (), |_| async { get_library_stats().await });
+
+    view! {
+        <h1>"Library statistics"</h1>
+        <Suspense fallback=move || view! {<p>"Loading statistics"</p>}>
+            <ErrorBoundary fallback=|_| {view! {<p>"Something went wrong"</p>}}>
+                {move || stats.get().map(|res| res.map(|stats| view! {
+                    <div class="stats">
+                        <div class="stats-summary">
+                            <p>{stats.photos}" photos"</p>
+                            <p>{format_bytes(stats.storage_bytes)}" used"</p>
+                            <p>{stats.without_capture_date}" without capture date"</p>
+                            <p>{stats.without_people}" without tagged people"</p>
+                            <p>{stats.with_unnamed_faces}" with untagged faces"</p>
+                        </div>
+                        <CountTable title="Photos per uploader" counts=stats.per_uploader/>
+                        <CountTable title="Uploads per month" counts=stats.per_month/>
+                        <CountTable title="Top tags" counts=stats.top_tags/>
+                        <CountTable title="Most tagged people" counts=stats.top_people/>
+                    </div>
+                }))}
+            </ErrorBoundary>
+        </Suspense>
+    }
+}
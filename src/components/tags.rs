@@ -0,0 +1,334 @@
+#[cfg(feature = "ssr")]
+use crate::auth;
+use crate::components::home_page::Tag;
+use leptos::html::{Input, Select};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+//Hierarchical tags are paths like `travel/norway/bergen`
+pub const TAG_SEPARATOR: char = '/';
+
+/// Lowercase a tag, replace spaces with `-` and drop empty path segments.
+/// Returns `None` if nothing valid is left.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let segments: Vec<String> = tag
+        .split(TAG_SEPARATOR)
+        .map(|segment| segment.trim().to_lowercase().replace(' ', "-"))
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    let valid = !segments.is_empty()
+        && segments.iter().all(|segment| {
+            segment
+                .chars()
+                .all(|c| c.is_alphabetic() || c.is_numeric() || c == '-' || c == '_')
+        });
+
+    if valid {
+        Some(segments.join("/"))
+    } else {
+        None
+    }
+}
+
+/// Parents of a tag, outermost first. `a/b/c` gives `a` and `a/b`.
+pub fn ancestors(tag: &str) -> Vec<String> {
+    tag.match_indices(TAG_SEPARATOR)
+        .map(|(i, _)| tag[..i].to_string())
+        .collect()
+}
+
+/// SQL condition matching `column` against a tag and all of its descendants.
+/// Takes the three binds from `subtree_binds`.
+pub fn subtree_condition(column: &str) -> String {
+    format!("({0} = ? OR ({0} > ? AND {0} < ?))", column)
+}
+
+/// `'0'` is the character after `'/'`, so this range covers every `tag/...`.
+pub fn subtree_binds(tag: &str) -> [String; 3] {
+    [tag.to_string(), format!("{}/", tag), format!("{}0", tag)]
+}
+
+/// Order in which children follow their parent.
+pub fn tree_order(a: &str, b: &str) -> std::cmp::Ordering {
+    a.split(TAG_SEPARATOR).cmp(b.split(TAG_SEPARATOR))
+}
+
+/// Sort tags so that children follow their parent.
+pub fn sort_tree(tags: &mut [Tag]) {
+    tags.sort_by(|a, b| tree_order(&a.tag_string, &b.tag_string));
+}
+
+/// Tag indented by its depth, for showing the tree in a select. The full path is kept
+/// so selected tags stay unambiguous.
+pub fn render_tree(tag: &str) -> String {
+    let depth = tag.matches(TAG_SEPARATOR).count();
+    if depth == 0 {
+        tag.to_string()
+    } else {
+        format!("{}└ {}", "\u{a0}\u{a0}".repeat(depth - 1), tag)
+    }
+}
+
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagUsage {
+    pub tag_string: String,
+    /// Number of photos with exactly this tag
+    pub count: i64,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{ancestors, normalize_tag};
+    use sqlx::{Sqlite, SqliteConnection, Transaction};
+
+    /// Normalize a tag, create it and its parents if needed and attach it to a file.
+    /// Invalid tags are skipped.
+    pub async fn attach_tag(
+        file_id: &str,
+        tag: &str,
+        conn: &mut SqliteConnection,
+    ) -> Result<(), sqlx::Error> {
+        let Some(tag) = normalize_tag(tag) else {
+            return Ok(());
+        };
+
+        for tag in ancestors(&tag).iter().chain(std::iter::once(&tag)) {
+            sqlx::query("INSERT OR IGNORE INTO tags (tagString) VALUES (?)")
+                .bind(tag)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        sqlx::query("INSERT OR IGNORE INTO tagFile (tagString, fileID) VALUES (?, ?)")
+            .bind(tag)
+            .bind(file_id)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Move a tag and all of its descendants to a new name, merging with any
+    /// tags that already exist there.
+    pub async fn move_subtree(
+        from: &str,
+        to: &str,
+        tx: &mut Transaction<'_, Sqlite>,
+    ) -> Result<(), sqlx::Error> {
+        let subtree: Vec<String> = sqlx::query_scalar(&format!(
+            "SELECT tagString FROM tags WHERE {}",
+            super::subtree_condition("tagString")
+        ))
+        .bind(from)
+        .bind(format!("{}/", from))
+        .bind(format!("{}0", from))
+        .fetch_all(&mut **tx)
+        .await?;
+
+        for old in subtree {
+            let new = format!("{}{}", to, &old[from.len()..]);
+
+            for tag in ancestors(&new).iter().chain(std::iter::once(&new)) {
+                sqlx::query("INSERT OR IGNORE INTO tags (tagString) VALUES (?)")
+                    .bind(tag)
+                    .execute(&mut **tx)
+                    .await?;
+            }
+
+            // Photos that already have the new tag keep a single copy
+            sqlx::query("UPDATE OR IGNORE tagFile SET tagString = ? WHERE tagString = ?")
+                .bind(&new)
+                .bind(&old)
+                .execute(&mut **tx)
+                .await?;
+            sqlx::query("DELETE FROM tagFile WHERE tagString = ?")
+                .bind(&old)
+                .execute(&mut **tx)
+                .await?;
+            sqlx::query("DELETE FROM tags WHERE tagString = ?")
+                .bind(&old)
+                .execute(&mut **tx)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+//All tags with how many photos use them
+#[server(GetTagUsage, "/api")]
+pub async fn get_tag_usage() -> Result<Vec<TagUsage>, ServerFnError> {
+    auth::authorized("admin").await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let mut usage = sqlx::query_as::<_, TagUsage>(
+        "SELECT t.tagString AS tag_string, COUNT(tf.fileID) AS count
+        FROM tags t
+        LEFT JOIN tagFile tf ON tf.tagString = t.tagString
+        GROUP BY t.tagString",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    usage.sort_by(|a, b| tree_order(&a.tag_string, &b.tag_string));
+
+    Ok(usage)
+}
+
+#[cfg(feature = "ssr")]
+async fn move_tag(from: String, to: String, merge: bool) -> Result<(), ServerFnError> {
+    auth::authorized("admin").await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    //Tags stored before they were normalized can still be renamed and merged
+    let Some(to) = normalize_tag(&to) else {
+        return Err(ServerFnError::ServerError("Invalid tag".to_string()));
+    };
+    let found: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM tags WHERE tagString = ?)")
+        .bind(&from)
+        .fetch_one(&pool)
+        .await?;
+    if !found {
+        return Err(ServerFnError::ServerError(format!(
+            "The tag {} does not exist",
+            from
+        )));
+    }
+    if from == to || to.starts_with(&format!("{}/", from)) {
+        return Err(ServerFnError::ServerError(
+            "A tag can not be moved into itself".to_string(),
+        ));
+    }
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM tags WHERE tagString = ?)")
+        .bind(&to)
+        .fetch_one(&pool)
+        .await?;
+    if exists && !merge {
+        return Err(ServerFnError::ServerError(format!(
+            "The tag {} already exists, merge the tags instead",
+            to
+        )));
+    }
+    if !exists && merge {
+        return Err(ServerFnError::ServerError(format!(
+            "The tag {} does not exist",
+            to
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    ssr::move_subtree(&from, &to, &mut tx).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+//Rename a tag and its descendants
+#[server(RenameTag, "/api")]
+pub async fn rename_tag(from: String, to: String) -> Result<(), ServerFnError> {
+    move_tag(from, to, false).await
+}
+
+//Move all photos of a tag and its descendants to another existing tag
+#[server(MergeTags, "/api")]
+pub async fn merge_tags(from: String, into: String) -> Result<(), ServerFnError> {
+    move_tag(from, into, true).await
+}
+
+//Delete tags that neither they nor their descendants are used by any photo
+#[server(DeleteUnusedTags, "/api")]
+pub async fn delete_unused_tags() -> Result<u64, ServerFnError> {
+    auth::authorized("admin").await?;
+    use crate::db::ssr::pool;
+    let pool = pool()?;
+
+    let res = sqlx::query(
+        "DELETE FROM tags WHERE NOT EXISTS (
+            SELECT 1 FROM tagFile tf WHERE tf.tagString = tags.tagString
+            OR (tf.tagString > tags.tagString || '/' AND tf.tagString < tags.tagString || '0'))",
+    )
+    .execute(&pool)
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
+#[component]
+pub fn TagPanel() -> impl IntoView {
+    let usage = create_resource(|| (), |_| async { get_tag_usage().await });
+    let (message, set_message) = create_signal(None::<String>);
+
+    let report = move |res: Result<(), ServerFnError>| {
+        match res {
+            Ok(_) => set_message(None),
+            Err(e) => set_message(Some(e.to_string())),
+        }
+        usage.refetch();
+    };
+
+    let delete_unused = move |_| {
+        spawn_local(async move {
+            match delete_unused_tags().await {
+                Ok(n) => set_message(Some(format!("Deleted {} unused tags", n))),
+                Err(e) => set_message(Some(e.to_string())),
+            }
+            usage.refetch();
+        })
+    };
+
+    view! {
+        <h2>"Tags"</h2>
+        <button on:click=delete_unused style="width: 200px;">"Delete unused tags"</button>
+        <Show when=move || message().is_some()>
+            <p>{message().unwrap()}</p>
+        </Show>
+        <Suspense fallback=move || view! {<p>"Loading tags"</p>}>
+            <ErrorBoundary fallback=|_| {view! {<p>"Something went wrong"</p>}}>
+                {move || usage.get().map(|res| res.map(|tags| {
+                    let all_tags = store_value(tags.clone());
+                    view! {
+                        <div class="userlist">
+                        {tags.into_iter().map(|tag| {
+                            let name_ref = create_node_ref::<Input>();
+                            let merge_ref = create_node_ref::<Select>();
+                            let tag_string = store_value(tag.tag_string.clone());
+                            view! {
+                                <div class="user-item">
+                                    <p>{render_tree(&tag.tag_string)}" ("{tag.count}")"</p>
+                                    <div class="buttons">
+                                        <input type="text" placeholder="New name" _ref=name_ref/>
+                                        <button on:click=move |_| {
+                                            let to = name_ref.get_untracked().unwrap().value();
+                                            spawn_local(async move {
+                                                report(rename_tag(tag_string.get_value(), to).await);
+                                            })
+                                        }>"Rename"</button>
+                                        <select _ref=merge_ref>
+                                            {all_tags.get_value().into_iter()
+                                                .filter(|other| other.tag_string != tag_string.get_value())
+                                                .map(|other| view! {
+                                                    <option value={other.tag_string.clone()}>{other.tag_string}</option>
+                                                }).collect_view()}
+                                        </select>
+                                        <button on:click=move |_| {
+                                            let into = merge_ref.get_untracked().unwrap().value();
+                                            spawn_local(async move {
+                                                report(merge_tags(tag_string.get_value(), into).await);
+                                            })
+                                        }>"Merge into"</button>
+                                    </div>
+                                </div>
+                            }
+                        }).collect_view()}
+                        </div>
+                    }
+                }))}
+            </ErrorBoundary>
+        </Suspense>
+    }
+}
@@ -1,13 +1,16 @@
 #[cfg(feature = "ssr")]
 use crate::auth;
+use crate::components::albums::get_albums;
+use crate::components::faces::UnnamedFaces;
 use crate::components::home_page::{get_tags, Tag};
-use crate::components::users::{get_user_list_sans_admin, UserInfo};
+use crate::components::people::{get_people, PersonInfo};
 use futures::future;
 use image::DynamicImage;
 use leptonic::components::select::{Multiselect, OptionalSelect};
-use leptos::{html::Input, *};
-#[cfg(feature = "ssr")]
-use rustface::{Detector, ImageData};
+use leptos::{
+    html::{Input, Select},
+    *,
+};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::UnwrapThrowExt;
 use web_sys::*;
@@ -25,15 +28,16 @@ pub struct Bbox {
     pub h: u32,
 }
 
-#[cfg(feature = "ssr")]
 impl Bbox {
-    fn rect(r: &rustface::Rectangle) -> Bbox {
-        Bbox {
-            x: r.x() as u32,
-            y: r.y() as u32,
-            w: r.width(),
-            h: r.height(),
+    /// Whether the boxes share more than a quarter of the smaller box.
+    pub fn overlaps(&self, other: &Bbox) -> bool {
+        let w = (self.x + self.w).min(other.x + other.w) as i64 - self.x.max(other.x) as i64;
+        let h = (self.y + self.h).min(other.y + other.h) as i64 - self.y.max(other.y) as i64;
+        if w <= 0 || h <= 0 {
+            return false;
         }
+        let smaller = (self.w as i64 * self.h as i64).min(other.w as i64 * other.h as i64);
+        w * h * 4 > smaller
     }
 }
 
@@ -44,50 +48,19 @@ pub struct Person {
     pub id: i64,
 }
 
-#[server(Faces, "/api")]
-pub async fn faces(image_b64: String) -> Result<Vec<Bbox>, ServerFnError> {
-    auth::logged_in().await?;
-
-    let mut detector = match rustface::create_detector("model.bin") {
-        Ok(detector) => detector,
-        Err(_) => {
-            return Err(ServerFnError::new(
-                "Failed to create face detector".to_string(),
-            ));
-        }
-    };
-
-    detector.set_min_face_size(20);
-    detector.set_score_thresh(2.0);
-    detector.set_pyramid_scale_factor(0.8);
-    detector.set_slide_window_step(4, 4);
-
-    let image = decode_image(image_b64).to_luma8();
-    let faces = detect_faces(&mut *detector, &image);
-    Ok(faces)
-}
-
 pub fn decode_image(encoded_string: String) -> image::DynamicImage {
     let bytes = base64::decode(encoded_string).expect("Failed to decode image");
     image::load_from_memory(&bytes).expect("Failed to load image")
 }
 
-#[cfg(feature = "ssr")]
-fn detect_faces(detector: &mut dyn Detector, gray: &image::GrayImage) -> Vec<Bbox> {
-    let (width, height) = gray.dimensions();
-    let image = ImageData::new(gray, width, height);
-    let faces = detector.detect(&image);
-
-    faces.iter().map(|fi| Bbox::rect(fi.bbox())).collect()
-}
-
 #[server(Upload, "/api", "Cbor")]
 pub async fn upload_media_server(
     filename: String,
     encoded_string: String,
     people: Vec<Person>,
     tags: Vec<Tag>,
-) -> Result<(), ServerFnError> {
+    album: Option<String>,
+) -> Result<(String, usize), ServerFnError> {
     let user = auth::logged_in().await?;
     use crate::db::ssr::pool;
     use rand::Rng;
@@ -96,6 +69,11 @@ pub async fn upload_media_server(
 
     let pool = pool()?;
 
+    // Check album access before anything is stored
+    if let Some(album_id) = &album {
+        crate::components::albums::ssr::require_contributor(album_id, user.id, &pool).await?;
+    }
+
     let album_path = "./app/data/album";
     if !Path::new(album_path).exists() {
         let _ = fs::create_dir_all(album_path)?;
@@ -108,11 +86,12 @@ pub async fn upload_media_server(
     let path = format!("{}/{}.{}", album_path, uuid, file_ext);
     let bytes = base64::decode(encoded_string).expect_throw("Failed to decode base64");
 
-    fs::write(&path, bytes).expect_throw("Failed to write file");
+    let coordinates = crate::components::map::ssr::exif_coordinates(&bytes);
+    fs::write(&path, &bytes).expect_throw("Failed to write file");
 
     sqlx::query(
-        "INSERT INTO files (id, path, uploadDate, createdDate, uploadedBy) 
-        VALUES (?, ?, datetime('now', 'localtime'), ?, ?)",
+        "INSERT INTO files (id, path, uploadDate, createdDate, uploadedBy, latitude, longitude) 
+        VALUES (?, ?, datetime('now', 'localtime'), ?, ?, ?, ?)",
     ) //SELECT date('now', 'localtime');
     .bind(&uuid)
     .bind(path)
@@ -127,26 +106,30 @@ pub async fn upload_media_server(
         .to_string(),
     )
     .bind(user.id)
+    .bind(coordinates.map(|(lat, _)| lat))
+    .bind(coordinates.map(|(_, lon)| lon))
     .execute(&pool)
     .await?;
 
-    // Find / create users and attach them to image.
+    // Name the place the photo was taken
+    if let (Some((lat, lon)), Some(gazetteer)) = (
+        coordinates,
+        use_context::<std::sync::Arc<crate::gazetteer::Gazetteer>>(),
+    ) {
+        crate::gazetteer::ssr::geocode_file(&uuid, lat, lon, &gazetteer, &pool).await?;
+    }
+
+    if let Some(album_id) = album {
+        crate::components::albums::ssr::add_file(&album_id, &uuid, &pool).await?;
+    }
+
+    // Find / create people and attach them to image.
     for person in people {
         if person.name == "".to_string() {
             continue; // Skip this person.
         }
 
-        let user = match auth::ssr::SqlUser::get_from_username(person.name.clone(), &pool).await {
-            Some(u) => u.id,
-            None => {
-                let res = sqlx::query("INSERT INTO users (username) VALUES (?)")
-                    .bind(person.name)
-                    .execute(&pool)
-                    .await?;
-
-                res.last_insert_rowid()
-            }
-        };
+        let person_id = crate::components::people::ssr::person_id(&person.name, &pool).await?;
 
         let binds: Vec<Option<u32>> = match person.bounds {
             Some(b) => vec![b.x, b.y, b.w, b.h]
@@ -158,9 +141,9 @@ pub async fn upload_media_server(
 
         // Insert name tags
         let mut q = sqlx::query(
-            "INSERT INTO userFile (userID, fileID, x, y, width, height) VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO userFile (personID, fileID, x, y, width, height) VALUES (?, ?, ?, ?, ?, ?)",
         )
-        .bind(user)
+        .bind(person_id)
         .bind(&uuid);
         for b in binds {
             q = q.bind(b);
@@ -169,40 +152,33 @@ pub async fn upload_media_server(
     }
 
     // Find / create tags and attach them to image
-    for mut tag in tags {
-        if tag.tag_string == "".to_string() {
-            continue; // Skip this tag
-        }
+    let mut conn = pool.acquire().await?;
+    for tag in tags {
+        crate::components::tags::ssr::attach_tag(&uuid, &tag.tag_string, &mut conn).await?;
+    }
 
-        // Spaces not allowed in tags
-        tag.tag_string = str::replace(&tag.tag_string, " ", "-");
-
-        // Lowercase tags
-        tag.tag_string = tag.tag_string.to_lowercase();
-
-        // Find or create new tag in db. Result is irrelevant, if it failed, the tag already
-        // existed. If it succeeded, continue with new tag anyways.
-        let _ = sqlx::query("INSERT INTO tags (tagString) VALUES (?)")
-            .bind(&tag.tag_string)
-            .bind(&uuid)
-            .execute(&pool)
-            .await;
-
-        sqlx::query("INSERT INTO tagFile (tagString, fileID) VALUES (?, ?)")
-            .bind(tag.tag_string)
-            .bind(&uuid)
-            .execute(&pool)
-            .await?;
+    // Find faces for the uploader to name afterwards, the upload is kept if this fails
+    let mut faces = 0;
+    if let Some(detectors) = use_context::<std::sync::Arc<crate::face_detector::FaceDetectors>>() {
+        use crate::components::faces::ssr::{detect_faces, embed_marked_faces};
+        match detect_faces(&uuid, bytes, &detectors, &pool).await {
+            Ok(found) => faces = found,
+            Err(e) => eprintln!("Face detection failed for {}: {}", uuid, e),
+        }
+        if let Err(e) = embed_marked_faces(&uuid, &detectors, &pool).await {
+            eprintln!("Face embedding failed for {}: {}", uuid, e);
+        }
     }
 
-    Ok(())
+    Ok((uuid, faces))
 }
 
 async fn upload(
     payload: Vec<(String, String, RwSignal<Vec<Person>>, RwSignal<Vec<Tag>>)>,
+    album: Option<String>,
     set_done: WriteSignal<usize>,
     done_count: ReadSignal<usize>,
-) -> Result<(), ServerFnError> {
+) -> Result<Vec<String>, ServerFnError> {
     if payload.is_empty() {
         return Err(ServerFnError::new("No files to upload".to_string()));
     }
@@ -215,19 +191,22 @@ async fn upload(
             encoded_string,
             people.get_untracked(),
             tags.get_untracked(),
+            album.clone(),
             set_done,
             done_count,
         ));
     }
 
-    let results = future::join_all(calls).await;
-    for result in results {
-        if let Err(e) = result {
-            return Err(e);
-        }
-    }
-
-    Ok(())
+    //Ids of the stored files with faces to name
+    let uploaded = future::join_all(calls)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(uploaded
+        .into_iter()
+        .filter(|(_, faces)| *faces > 0)
+        .map(|(file_id, _)| file_id)
+        .collect())
 }
 
 async fn upload_wrapper(
@@ -235,14 +214,15 @@ async fn upload_wrapper(
     encoded_string: String,
     names: Vec<Person>,
     tags: Vec<Tag>,
+    album: Option<String>,
     set_done: WriteSignal<usize>,
     done_count: ReadSignal<usize>,
-) -> Result<(), ServerFnError> {
-    return match upload_media_server(filename, encoded_string, names, tags).await {
-        Ok(_) => {
+) -> Result<(String, usize), ServerFnError> {
+    return match upload_media_server(filename, encoded_string, names, tags, album).await {
+        Ok(uploaded) => {
             set_done(done_count.get_untracked() + 1);
             logging::log!("{}", done_count.get_untracked());
-            Ok(())
+            Ok(uploaded)
         }
         Err(e) => Err(e),
     };
@@ -259,10 +239,14 @@ pub fn UploadMedia() -> impl IntoView {
     let (count, set_count) = create_signal(0);
 
     let (error, set_error) = create_signal(String::new());
+    //Uploaded files with faces found in them left to name
+    let (uploaded, set_uploaded) = create_signal(Vec::<String>::new());
 
     let input_ref = create_node_ref::<Input>();
+    let album_ref = create_node_ref::<Select>();
 
     let on_change = move |ev: leptos::ev::Event| {
+        set_uploaded(Vec::new());
         set_done(0);
         set_memory(0);
         set_error("".to_string());
@@ -284,9 +268,12 @@ pub fn UploadMedia() -> impl IntoView {
         spawn_local(async move {
             set_done(0);
             set_count(memory_count.get_untracked() as u32);
-            match upload(media.get_untracked(), set_done, done_count).await {
-                Ok(_) => {
+            let album = album_ref.get_untracked().unwrap().value();
+            let album = if album.is_empty() { None } else { Some(album) };
+            match upload(media.get_untracked(), album, set_done, done_count).await {
+                Ok(files) => {
                     logging::log!("OK");
+                    set_uploaded(files);
                     let input_elem = input_ref.get().unwrap();
                     input_elem.set_files(None);
                     input_elem.set_value("");
@@ -311,9 +298,10 @@ pub fn UploadMedia() -> impl IntoView {
 
     let users = create_rw_signal(vec![]);
     let tag_options = create_rw_signal(vec![]);
+    let albums = create_rw_signal(vec![]);
     spawn_local(async move {
-        match get_user_list_sans_admin().await {
-            Ok(u) => users.set(u),
+        match get_people().await {
+            Ok(p) => users.set(p),
             Err(e) => logging::log!("{}", e),
         };
 
@@ -321,12 +309,25 @@ pub fn UploadMedia() -> impl IntoView {
             Ok(t) => tag_options.set(t),
             Err(e) => logging::log!("{}", e),
         };
+
+        match get_albums().await {
+            Ok(a) => albums.set(a),
+            Err(e) => logging::log!("{}", e),
+        };
     });
 
     view! {
         <input id="file_input" _ref=input_ref type="file" multiple="multiple" accept="image/png, image/gif, image/jpeg, image/tiff"
             on:change=on_change
         />
+        <select _ref=album_ref>
+            <option value="">"No album"</option>
+            {move || albums.get().into_iter()
+                .filter(|album| album.can_contribute())
+                .map(|album| view! {
+                    <option value={album.id}>{album.name}</option>
+                }).collect_view()}
+        </select>
         <button on:click=on_click>"Upload"</button>
         <p>{ move ||
             match count() {
@@ -341,6 +342,17 @@ pub fn UploadMedia() -> impl IntoView {
         </p>
 
         <p>{ move || error() }</p>
+        <Show when=move || !uploaded.get().is_empty()>
+            <div class="upload-faces">
+                <For
+                    each=uploaded
+                    key=|file_id| file_id.clone()
+                    children=|file_id| view! {
+                        <UnnamedFaces file_id=file_id on_named=|_| ()/>
+                    }
+                />
+            </div>
+        </Show>
         <div class="upload-content">
                 {
                     move || if !media().is_empty() {
@@ -371,7 +383,7 @@ pub fn UploadMedia() -> impl IntoView {
                                                 key=|idx| idx.id
                                                 children=move |idx| {
 
-                                                    let (get_name, set_name) = create_signal(Option::<UserInfo>::None);
+                                                    let (get_name, set_name) = create_signal(Option::<PersonInfo>::None);
                                                     let _ = create_resource(
                                                         get_name,
                                                         // every time `get_name` changes, this will run
@@ -381,7 +393,7 @@ pub fn UploadMedia() -> impl IntoView {
                                                                 None => return,
                                                             };
 
-                                                            name_list.update(|vs| vs[idx.id as usize].name = v.username)
+                                                            name_list.update(|vs| vs[idx.id as usize].name = v.name)
                                                         },
                                                     );
 
@@ -391,10 +403,10 @@ pub fn UploadMedia() -> impl IntoView {
                                                             <img class="profilepicture" src={format!("data:image/webp;base64,{}", img_from_bounds(&img, idx.bounds))} />
                                                             <OptionalSelect class="person"
                                                                 options=users
-                                                                search_text_provider=move |o: UserInfo| o.username
-                                                                render_option=move |o: UserInfo| o.username
+                                                                search_text_provider=move |o: PersonInfo| o.name
+                                                                render_option=move |o: PersonInfo| o.name
                                                                 selected=get_name
-                                                                add=move |v: String| users.update(|ns| ns.push(UserInfo{id:-1, username:v}))
+                                                                add=move |v: String| users.update(|ns| ns.push(PersonInfo{id:-1, name:v}))
                                                                 set_selected=set_name
                                                                 allow_deselect=true
                                                             />
@@ -410,7 +422,7 @@ pub fn UploadMedia() -> impl IntoView {
                                             <Multiselect class="mselect"
                                                 options = tag_options
                                                 search_text_provider=move |o: Tag| o.tag_string
-                                                render_option=move |o: Tag| o.tag_string
+                                                render_option=move |o: Tag| crate::components::tags::render_tree(&o.tag_string)
                                                 selected=get_tags
                                                 add=move |v: String| tag_options.update(|ts| ts.push(Tag{tag_string: v}))
                                                 set_selected=set_tags
@@ -520,31 +532,11 @@ async fn convert_file_to_b64(
         .await
         .expect_throw("Failed to read file");
     let encoded_string = base64::encode(&bytes);
-    let encoded_string_thread = encoded_string.clone();
+    //Faces are found by the server after upload
     let names: RwSignal<Vec<Person>> = create_rw_signal(Vec::new());
     let tags: RwSignal<Vec<Tag>> = create_rw_signal(Vec::new());
 
-    spawn_local(async move {
-        let faces = match faces(encoded_string_thread).await {
-            Ok(c) => c,
-            Err(_err) => Vec::new(),
-        };
-
-        logging::log!("{}", faces.len());
-
-        set_memory(memory_count.get_untracked() + 1);
-
-        let mut names_init: Vec<Person> = Vec::new();
-        for i in 0..faces.len() {
-            names_init.push(Person {
-                name: "".to_string(),
-                id: i as i64,
-                bounds: Some(faces[i].clone()),
-            });
-        }
-
-        names.set(names_init);
-    });
+    set_memory(memory_count.get_untracked() + 1);
 
     (gloo_file.name(), encoded_string, names, tags)
 }
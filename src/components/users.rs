@@ -12,17 +12,39 @@ pub struct UserInfo {
     pub username: String,
 }
 
-#[server(GetUserList, "/api")]
-pub async fn get_user_list_sans_admin() -> Result<Vec<UserInfo>, ServerFnError> {
+//Signed up accounts other than the requesting user
+#[server(GetAccountList, "/api")]
+pub async fn get_account_list() -> Result<Vec<UserInfo>, ServerFnError> {
+    let user = auth::logged_in().await?;
+    use crate::db::ssr::pool;
+
+    let pool = pool()?;
+
+    let users = sqlx::query_as::<_, UserInfo>(
+        "SELECT id, username FROM users WHERE signed_up = 1 AND id != ? ORDER BY username",
+    )
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(users)
+}
+
+//Users that have uploaded at least one image
+#[server(GetUploaders, "/api")]
+pub async fn get_uploaders() -> Result<Vec<UserInfo>, ServerFnError> {
     auth::logged_in().await?;
     use crate::db::ssr::pool;
 
     let pool = pool()?;
 
-    let users =
-        sqlx::query_as::<_, UserInfo>("SELECT id, username FROM users WHERE username != 'admin'")
-            .fetch_all(&pool)
-            .await?;
+    let users = sqlx::query_as::<_, UserInfo>(
+        "SELECT DISTINCT u.id, u.username FROM users u
+        INNER JOIN files f ON f.uploadedBy = u.id
+        ORDER BY u.username",
+    )
+    .fetch_all(&pool)
+    .await?;
 
     Ok(users)
 }
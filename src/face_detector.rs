@@ -0,0 +1,343 @@
+use crate::components::upload::Bbox;
+use crate::face_embedding::{ssr::FaceEmbedder, DEFAULT_MATCH_THRESHOLD};
+use rustface::{Detector, ImageData};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+#[derive(Debug, Error)]
+pub enum DetectorError {
+    #[error("Could not load face model {0}")]
+    Model(String),
+    #[error("Invalid face detector setting {name}={value}")]
+    Config { name: &'static str, value: String },
+    #[error("Could not read image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Face embedding failed: {0}")]
+    Embedding(String),
+    #[error("Face detection failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// Face box with the detector score, higher is more certain.
+#[derive(Clone, Debug)]
+pub struct DetectedFace {
+    pub bounds: Bbox,
+    pub confidence: f64,
+    /// Only set when a face embedding model is loaded
+    pub embedding: Option<Vec<f32>>,
+    /// Small WebP crop of the face, see [`face_crop`]
+    pub crop: Option<Vec<u8>>,
+}
+
+/// Longest side of a stored face crop, in pixels.
+pub const CROP_SIZE: u32 = 128;
+
+/// Parameters passed on to every detector in the pool.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DetectorConfig {
+    /// Smallest face found, in pixels. Must be at least 20.
+    pub min_face_size: u32,
+    pub score_thresh: f64,
+    /// Between 0 and 1, larger is slower and finds more faces.
+    pub pyramid_scale_factor: f32,
+    /// Horizontal and vertical step of the sliding window.
+    pub slide_window_step: (u32, u32),
+    /// Number of detectors, which is also the number of images analysed at once.
+    pub detectors: usize,
+    /// Least similarity between faces for suggesting a person, between 0 and 1.
+    pub match_threshold: f32,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        DetectorConfig {
+            min_face_size: 20,
+            score_thresh: 2.0,
+            pyramid_scale_factor: 0.8,
+            slide_window_step: (4, 4),
+            detectors: std::thread::available_parallelism()
+                .map_or(1, |n| n.get())
+                .min(4),
+            match_threshold: DEFAULT_MATCH_THRESHOLD,
+        }
+    }
+}
+
+impl DetectorConfig {
+    /// Defaults overridden by `FACE_MIN_SIZE`, `FACE_SCORE_THRESHOLD`,
+    /// `FACE_PYRAMID_SCALE`, `FACE_WINDOW_STEP` (`x` or `x,y`), `FACE_DETECTORS`
+    /// and `FACE_MATCH_THRESHOLD`.
+    pub fn from_env() -> Result<Self, DetectorError> {
+        DetectorConfig::from_vars(|name| std::env::var(name).ok())
+    }
+
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, DetectorError> {
+        let mut config = DetectorConfig::default();
+
+        for name in [
+            "FACE_MIN_SIZE",
+            "FACE_SCORE_THRESHOLD",
+            "FACE_PYRAMID_SCALE",
+            "FACE_WINDOW_STEP",
+            "FACE_DETECTORS",
+            "FACE_MATCH_THRESHOLD",
+        ] {
+            let Some(value) = var(name) else {
+                continue;
+            };
+            let error = || DetectorError::Config {
+                name,
+                value: value.clone(),
+            };
+            let value = value.trim();
+
+            match name {
+                "FACE_MIN_SIZE" => {
+                    config.min_face_size = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|&s| s >= 20)
+                        .ok_or_else(error)?
+                }
+                "FACE_SCORE_THRESHOLD" => {
+                    config.score_thresh = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|&t| t >= 0.0)
+                        .ok_or_else(error)?
+                }
+                "FACE_PYRAMID_SCALE" => {
+                    config.pyramid_scale_factor = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|&f| f > 0.0 && f < 1.0)
+                        .ok_or_else(error)?
+                }
+                "FACE_WINDOW_STEP" => {
+                    let steps: Vec<u32> = value
+                        .split(',')
+                        .map(|s| s.trim().parse::<u32>().ok().filter(|&s| s > 0))
+                        .collect::<Option<_>>()
+                        .ok_or_else(error)?;
+                    config.slide_window_step = match steps[..] {
+                        [step] => (step, step),
+                        [x, y] => (x, y),
+                        _ => return Err(error()),
+                    };
+                }
+                "FACE_MATCH_THRESHOLD" => {
+                    config.match_threshold = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|t| (0.0..=1.0).contains(t))
+                        .ok_or_else(error)?
+                }
+                _ => {
+                    config.detectors = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(error)?
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Initialised detectors shared by all requests. Detection runs on the
+/// blocking thread pool, at most one image per detector at a time.
+pub struct FaceDetectors {
+    model_path: PathBuf,
+    config: DetectorConfig,
+    idle: Mutex<Vec<Box<dyn Detector>>>,
+    permits: Semaphore,
+    embedder: Option<FaceEmbedder>,
+}
+
+impl std::fmt::Debug for FaceDetectors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FaceDetectors")
+            .field("model_path", &self.model_path)
+            .field("config", &self.config)
+            .field("embedder", &self.embedder)
+            .finish_non_exhaustive()
+    }
+}
+
+fn create_detector(
+    model_path: &Path,
+    config: &DetectorConfig,
+) -> Result<Box<dyn Detector>, DetectorError> {
+    let path = model_path.to_string_lossy();
+    let mut detector =
+        rustface::create_detector(&path).map_err(|_| DetectorError::Model(path.to_string()))?;
+
+    detector.set_min_face_size(config.min_face_size);
+    detector.set_score_thresh(config.score_thresh);
+    detector.set_pyramid_scale_factor(config.pyramid_scale_factor);
+    let (x, y) = config.slide_window_step;
+    detector.set_slide_window_step(x, y);
+
+    Ok(detector)
+}
+
+impl FaceDetectors {
+    pub fn load(
+        model_path: impl AsRef<Path>,
+        config: DetectorConfig,
+    ) -> Result<Self, DetectorError> {
+        let model_path = model_path.as_ref().to_path_buf();
+        let idle = (0..config.detectors)
+            .map(|_| create_detector(&model_path, &config))
+            .collect::<Result<_, _>>()?;
+
+        Ok(FaceDetectors {
+            permits: Semaphore::new(config.detectors),
+            idle: Mutex::new(idle),
+            model_path,
+            config,
+            embedder: None,
+        })
+    }
+
+    /// Compute an embedding for every detected face with `embedder`.
+    pub fn with_embedder(mut self, embedder: FaceEmbedder) -> Self {
+        self.embedder = Some(embedder);
+        self
+    }
+
+    pub fn config(&self) -> &DetectorConfig {
+        &self.config
+    }
+
+    /// Whether faces get an embedding.
+    pub fn embeds(&self) -> bool {
+        self.embedder.is_some()
+    }
+
+    /// Faces in an encoded image, waiting for a free detector first.
+    pub async fn detect(&self, image: Vec<u8>) -> Result<Vec<DetectedFace>, DetectorError> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("Detector semaphore is never closed");
+
+        //A detector is lost when detection panics, so make a new one
+        let idle = self.idle.lock().unwrap().pop();
+        let mut detector = match idle {
+            Some(detector) => detector,
+            None => create_detector(&self.model_path, &self.config)?,
+        };
+
+        let embedder = self.embedder.clone();
+        let (detector, faces) = tokio::task::spawn_blocking(move || {
+            let faces = image::load_from_memory(&image).map(|image| {
+                let mut faces = detect_faces(&mut *detector, &image.to_luma8());
+                for face in &mut faces {
+                    face.crop = face_crop(&image, &face.bounds);
+                    if let Some(embedder) = &embedder {
+                        face.embedding = embed_face(embedder, &image, &face.bounds);
+                    }
+                }
+                faces
+            });
+            (detector, faces)
+        })
+        .await?;
+
+        self.idle.lock().unwrap().push(detector);
+        Ok(faces?)
+    }
+
+    /// Embeddings of the faces inside `boxes` of an encoded image, `None` for
+    /// every box when no embedding model is loaded.
+    pub async fn embed(
+        &self,
+        image: Vec<u8>,
+        boxes: Vec<Bbox>,
+    ) -> Result<Vec<Option<Vec<f32>>>, DetectorError> {
+        let Some(embedder) = self.embedder.clone() else {
+            return Ok(vec![None; boxes.len()]);
+        };
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("Detector semaphore is never closed");
+
+        tokio::task::spawn_blocking(move || {
+            let image = image::load_from_memory(&image)?;
+            Ok(boxes
+                .iter()
+                .map(|bounds| embed_face(&embedder, &image, bounds))
+                .collect())
+        })
+        .await?
+    }
+}
+
+//A face that can not be embedded is kept without an embedding
+fn embed_face(
+    embedder: &FaceEmbedder,
+    image: &image::DynamicImage,
+    bounds: &Bbox,
+) -> Option<Vec<f32>> {
+    embedder
+        .embed(image, bounds)
+        .map_err(|e| eprintln!("{}", DetectorError::Embedding(e.to_string())))
+        .ok()
+}
+
+/// WebP crop of a face with some margin, scaled down to at most
+/// [`CROP_SIZE`] pixels.
+pub fn face_crop(image: &image::DynamicImage, bounds: &Bbox) -> Option<Vec<u8>> {
+    let margin = bounds.w.max(bounds.h) / 4;
+    let x = bounds.x.saturating_sub(margin).min(image.width());
+    let y = bounds.y.saturating_sub(margin).min(image.height());
+    let w = (bounds.w + margin * 2).min(image.width() - x);
+    let h = (bounds.h + margin * 2).min(image.height() - y);
+    if w == 0 || h == 0 {
+        return None;
+    }
+
+    let mut buf = Vec::new();
+    image
+        .crop_imm(x, y, w, h)
+        .thumbnail(CROP_SIZE, CROP_SIZE)
+        .write_to(
+            &mut std::io::Cursor::new(&mut buf),
+            image::ImageFormat::WebP,
+        )
+        .map_err(|e| eprintln!("Could not crop face: {}", e))
+        .ok()?;
+    Some(buf)
+}
+
+fn detect_faces(detector: &mut dyn Detector, gray: &image::GrayImage) -> Vec<DetectedFace> {
+    let (width, height) = gray.dimensions();
+    let image = ImageData::new(gray, width, height);
+    let faces = detector.detect(&image);
+
+    faces
+        .iter()
+        .map(|face| {
+            let r = face.bbox();
+            DetectedFace {
+                bounds: Bbox {
+                    x: r.x().max(0) as u32,
+                    y: r.y().max(0) as u32,
+                    w: r.width(),
+                    h: r.height(),
+                },
+                confidence: face.score(),
+                embedding: None,
+                crop: None,
+            }
+        })
+        .collect()
+}
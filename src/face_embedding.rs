@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+
+/// Faces this similar or more to a named face are suggested as that person.
+pub const DEFAULT_MATCH_THRESHOLD: f32 = 0.5;
+
+/// Side of the square face crop the embedding model takes.
+pub const INPUT_SIZE: u32 = 112;
+
+/// Person a face most likely belongs to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaceSuggestion {
+    pub person_id: i64,
+    pub name: String,
+    /// Cosine similarity to the closest named face of the person
+    pub similarity: f32,
+}
+
+/// Embeddings are stored as little endian `f32` blobs.
+pub fn to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub fn from_blob(blob: &[u8]) -> Option<Vec<f32>> {
+    if blob.is_empty() || !blob.len().is_multiple_of(4) {
+        return None;
+    }
+    Some(
+        blob.chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    )
+}
+
+/// Scale to unit length so the dot product is the cosine similarity.
+pub fn normalize(embedding: &mut [f32]) {
+    let length = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if length > 0.0 {
+        embedding.iter_mut().for_each(|v| *v /= length);
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let length = |v: &[f32]| v.iter().map(|v| v * v).sum::<f32>().sqrt();
+    match length(a) * length(b) {
+        l if l > 0.0 => dot / l,
+        _ => 0.0,
+    }
+}
+
+/// The person of the most similar named face, when it is at least `threshold` similar.
+pub fn nearest_person(
+    embedding: &[f32],
+    known: &[(i64, String, Vec<f32>)],
+    threshold: f32,
+) -> Option<FaceSuggestion> {
+    known
+        .iter()
+        .filter(|(_, _, other)| !other.is_empty() && other.len() == embedding.len())
+        .map(|(id, name, other)| (id, name, cosine_similarity(embedding, other)))
+        .filter(|(_, _, similarity)| *similarity >= threshold)
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(id, name, similarity)| FaceSuggestion {
+            person_id: *id,
+            name: name.clone(),
+            similarity,
+        })
+}
+
+/// Faces that look like the same person.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceGroup {
+    pub face_ids: Vec<i64>,
+    /// Normalized average embedding of the faces
+    pub centroid: Vec<f32>,
+}
+
+/// Group faces that are at least `threshold` similar to the average of a
+/// group, in one pass. The largest groups come first.
+pub fn cluster_faces(faces: &[(i64, Vec<f32>)], threshold: f32) -> Vec<FaceGroup> {
+    let mut groups: Vec<FaceGroup> = Vec::new();
+
+    for (id, embedding) in faces {
+        let mut embedding = embedding.clone();
+        normalize(&mut embedding);
+
+        let closest = groups
+            .iter_mut()
+            .map(|group| (cosine_similarity(&group.centroid, &embedding), group))
+            .filter(|(similarity, _)| *similarity >= threshold)
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+
+        match closest {
+            //The centroid is kept as a sum until all faces are added
+            Some((_, group)) => {
+                group.face_ids.push(*id);
+                group
+                    .centroid
+                    .iter_mut()
+                    .zip(&embedding)
+                    .for_each(|(sum, v)| *sum += v);
+            }
+            None => groups.push(FaceGroup {
+                face_ids: vec![*id],
+                centroid: embedding,
+            }),
+        }
+    }
+
+    for group in &mut groups {
+        normalize(&mut group.centroid);
+    }
+    groups.sort_by_key(|group| std::cmp::Reverse(group.face_ids.len()));
+    groups
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::{normalize, INPUT_SIZE};
+    use crate::components::upload::Bbox;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tract_onnx::prelude::*;
+
+    //Extra room around the detector box, relative to its size
+    const MARGIN: f32 = 0.2;
+
+    /// Face recognition model turning face crops into embeddings. It takes
+    /// a `1x3x112x112` RGB input and gives one embedding vector.
+    #[derive(Clone)]
+    pub struct FaceEmbedder {
+        model: Arc<TypedSimplePlan<TypedModel>>,
+    }
+
+    impl std::fmt::Debug for FaceEmbedder {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("FaceEmbedder").finish_non_exhaustive()
+        }
+    }
+
+    impl FaceEmbedder {
+        pub fn load(path: impl AsRef<Path>) -> TractResult<Self> {
+            let size = INPUT_SIZE as usize;
+            let model = tract_onnx::onnx()
+                .model_for_path(path)?
+                .with_input_fact(0, f32::fact([1, 3, size, size]).into())?
+                .into_optimized()?
+                .into_runnable()?;
+
+            Ok(FaceEmbedder {
+                model: Arc::new(model),
+            })
+        }
+
+        /// Normalized embedding of the face inside `bounds`.
+        pub fn embed(&self, image: &image::DynamicImage, bounds: &Bbox) -> TractResult<Vec<f32>> {
+            let margin = (bounds.w.max(bounds.h) as f32 * MARGIN) as u32;
+            let x = bounds.x.saturating_sub(margin);
+            let y = bounds.y.saturating_sub(margin);
+            let w = (bounds.w + margin * 2).min(image.width().saturating_sub(x));
+            let h = (bounds.h + margin * 2).min(image.height().saturating_sub(y));
+
+            let face = image
+                .crop_imm(x, y, w.max(1), h.max(1))
+                .resize_exact(
+                    INPUT_SIZE,
+                    INPUT_SIZE,
+                    image::imageops::FilterType::Triangle,
+                )
+                .to_rgb8();
+
+            let size = INPUT_SIZE as usize;
+            let input: Tensor =
+                tract_ndarray::Array4::from_shape_fn((1, 3, size, size), |(_, c, y, x)| {
+                    (face.get_pixel(x as u32, y as u32)[c] as f32 - 127.5) / 128.0
+                })
+                .into();
+
+            let output = self.model.run(tvec!(input.into()))?;
+            let mut embedding: Vec<f32> =
+                output[0].to_array_view::<f32>()?.iter().copied().collect();
+            normalize(&mut embedding);
+
+            Ok(embedding)
+        }
+    }
+}
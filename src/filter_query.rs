@@ -0,0 +1,367 @@
+use crate::components::tags;
+use crate::gazetteer::PlaceLevel;
+use std::fmt;
+use thiserror::Error;
+
+/// Syntax error in a filter expression. `position` is the character offset into the input.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{message} at position {position}")]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        ParseError {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Person(String),
+    Tag(String),
+    Uploader(String),
+    /// Inclusive range of capture years
+    Year(i32, i32),
+    Place(PlaceLevel, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Term(String, String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::And => write!(f, "AND"),
+            Token::Or => write!(f, "OR"),
+            Token::Not => write!(f, "NOT"),
+            Token::Term(field, value) => write!(f, "'{}:{}'", field, value),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '(' || c == ')' {
+            tokens.push((
+                i,
+                if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                },
+            ));
+            i += 1;
+            continue;
+        }
+
+        // A bare word: either a keyword or field:value
+        let start = i;
+        let mut word = String::new();
+        while i < chars.len() && !chars[i].is_whitespace() && !"():\"".contains(chars[i]) {
+            word.push(chars[i]);
+            i += 1;
+        }
+
+        if i < chars.len() && chars[i] == ':' {
+            if word.is_empty() {
+                return Err(ParseError::new(start, "Expected a field name before ':'"));
+            }
+            i += 1;
+
+            let mut value = String::new();
+            if i < chars.len() && chars[i] == '"' {
+                // Quoted values may contain spaces and parentheses
+                let quote = i;
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    value.push(chars[i]);
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(ParseError::new(quote, "Unterminated quote"));
+                }
+                i += 1;
+            } else {
+                while i < chars.len() && !chars[i].is_whitespace() && !"()\"".contains(chars[i]) {
+                    value.push(chars[i]);
+                    i += 1;
+                }
+            }
+
+            if value.trim().is_empty() {
+                return Err(ParseError::new(
+                    start,
+                    format!("Missing value for '{}'", word),
+                ));
+            }
+            tokens.push((
+                start,
+                Token::Term(word.to_lowercase(), value.trim().to_string()),
+            ));
+            continue;
+        }
+
+        match word.to_uppercase().as_str() {
+            "AND" => tokens.push((start, Token::And)),
+            "OR" => tokens.push((start, Token::Or)),
+            "NOT" => tokens.push((start, Token::Not)),
+            "" => return Err(ParseError::new(start, format!("Unexpected '{}'", c))),
+            _ => {
+                return Err(ParseError::new(
+                    start,
+                    format!(
+                        "Expected field:value but found '{}'. Try tag:{} or person:{}",
+                        word, word, word
+                    ),
+                ))
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_year(value: &str, position: usize) -> Result<i32, ParseError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| ParseError::new(position, format!("'{}' is not a year", value)))
+}
+
+fn make_term(field: &str, value: &str, position: usize) -> Result<Term, ParseError> {
+    match field {
+        "person" => Ok(Term::Person(value.to_string())),
+        "uploader" => Ok(Term::Uploader(value.to_string())),
+        "city" => Ok(Term::Place(PlaceLevel::City, value.to_string())),
+        "region" => Ok(Term::Place(PlaceLevel::Region, value.to_string())),
+        "country" => Ok(Term::Place(PlaceLevel::Country, value.to_string())),
+        "tag" => tags::normalize_tag(value)
+            .map(Term::Tag)
+            .ok_or_else(|| ParseError::new(position, format!("'{}' is not a valid tag", value))),
+        "year" => {
+            let (from, to) = match value.split_once("..") {
+                Some((from, to)) => (parse_year(from, position)?, parse_year(to, position)?),
+                None => {
+                    let year = parse_year(value, position)?;
+                    (year, year)
+                }
+            };
+            if from > to {
+                return Err(ParseError::new(
+                    position,
+                    format!("Year range {}..{} is reversed", from, to),
+                ));
+            }
+            Ok(Term::Year(from, to))
+        }
+        _ => Err(ParseError::new(
+            position,
+            format!(
+                "Unknown field '{}'. Use person, tag, uploader, year, city, region or country",
+                field
+            ),
+        )),
+    }
+}
+
+/// Deepest nesting of parentheses and NOT, so parsing can not overflow the stack.
+pub const MAX_DEPTH: usize = 32;
+/// Most terms in one expression, the SQL is built recursively.
+pub const MAX_TERMS: usize = 100;
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .map(|(position, _)| *position)
+            .unwrap_or(self.end)
+    }
+
+    // or := and (OR and)*
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.index += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    // and := unary (AND? unary)*, so adjacent terms are AND-ed
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.index += 1,
+                Some(Token::Not) | Some(Token::LParen) | Some(Token::Term(_, _)) => {}
+                _ => return Ok(expr),
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+    }
+
+    fn enter(&mut self, position: usize) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ParseError::new(
+                position,
+                format!("Filters can be nested at most {} levels deep", MAX_DEPTH),
+            ));
+        }
+        Ok(())
+    }
+
+    // unary := NOT unary | ( or ) | field:value
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        let position = self.position();
+        match self.tokens.get(self.index).map(|(_, token)| token.clone()) {
+            Some(Token::Not) => {
+                self.index += 1;
+                self.enter(position)?;
+                let expr = self.parse_unary()?;
+                self.depth -= 1;
+                Ok(Expr::Not(Box::new(expr)))
+            }
+            Some(Token::LParen) => {
+                self.index += 1;
+                self.enter(position)?;
+                let expr = self.parse_or()?;
+                self.depth -= 1;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(ParseError::new(
+                        self.position(),
+                        format!("Missing ')' for '(' at position {}", position),
+                    ));
+                }
+                self.index += 1;
+                Ok(expr)
+            }
+            Some(Token::Term(field, value)) => {
+                self.index += 1;
+                Ok(Expr::Term(make_term(&field, &value, position)?))
+            }
+            Some(token) => Err(ParseError::new(
+                position,
+                format!("Expected a filter but found {}", token),
+            )),
+            None => Err(ParseError::new(
+                position,
+                "Expected a filter but the expression ended",
+            )),
+        }
+    }
+}
+
+/// Parse a filter expression such as
+/// `person:anna AND (tag:beach OR tag:lake) AND NOT tag:work AND year:2019..2021`.
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let tokens = tokenize(input)?;
+    if let Some((position, _)) = tokens
+        .iter()
+        .filter(|(_, token)| matches!(token, Token::Term(_, _)))
+        .nth(MAX_TERMS)
+    {
+        return Err(ParseError::new(
+            *position,
+            format!("A filter can have at most {} terms", MAX_TERMS),
+        ));
+    }
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        end: input.chars().count(),
+        depth: 0,
+    };
+
+    let expr = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        return Err(ParseError::new(
+            parser.position(),
+            format!("Unexpected {}", token),
+        ));
+    }
+
+    Ok(expr)
+}
+
+impl Expr {
+    /// SQL condition on the files table aliased `f`. Values are pushed to `binds` in order.
+    pub fn to_sql(&self, binds: &mut Vec<String>) -> String {
+        match self {
+            Expr::And(left, right) => {
+                format!("({} AND {})", left.to_sql(binds), right.to_sql(binds))
+            }
+            Expr::Or(left, right) => {
+                format!("({} OR {})", left.to_sql(binds), right.to_sql(binds))
+            }
+            Expr::Not(expr) => format!("NOT {}", expr.to_sql(binds)),
+            Expr::Term(Term::Person(name)) => {
+                binds.push(name.clone());
+                "EXISTS (SELECT 1 FROM userFile uf INNER JOIN people p ON p.id = uf.personID WHERE uf.fileID = f.id AND p.name = ? COLLATE NOCASE)".to_string()
+            }
+            // A parent tag matches all of its descendants
+            Expr::Term(Term::Tag(tag)) => {
+                binds.extend(tags::subtree_binds(tag));
+                format!(
+                    "EXISTS (SELECT 1 FROM tagFile tf WHERE tf.fileID = f.id AND {})",
+                    tags::subtree_condition("tf.tagString")
+                )
+            }
+            Expr::Term(Term::Uploader(name)) => {
+                binds.push(name.clone());
+                "EXISTS (SELECT 1 FROM users u WHERE u.id = f.uploadedBy AND u.username = ? COLLATE NOCASE)".to_string()
+            }
+            Expr::Term(Term::Year(from, to)) => {
+                binds.push(from.to_string());
+                binds.push(to.to_string());
+                "(CAST(strftime('%Y', COALESCE(f.createdDate, f.uploadDate)) AS INTEGER) BETWEEN ? AND ?)".to_string()
+            }
+            Expr::Term(Term::Place(level, name)) => {
+                binds.push(name.clone());
+                format!("({} = ? COLLATE NOCASE)", level.column())
+            }
+        }
+    }
+}
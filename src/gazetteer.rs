@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use thiserror::Error;
+
+/// Photos further than this from every known place get no place name.
+pub const MAX_DISTANCE_KM: f64 = 100.0;
+
+const KM_PER_DEGREE: f64 = 111.32;
+
+#[derive(Debug, Error)]
+pub enum GazetteerError {
+    #[error("Could not read gazetteer: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Gazetteer line {line}: {message}")]
+    Line { line: usize, message: String },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Place {
+    pub city: String,
+    pub region: String,
+    pub country: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+//Place names are stored per level so each level can be filtered on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PlaceLevel {
+    Country,
+    Region,
+    City,
+}
+
+impl PlaceLevel {
+    /// Column of the files table aliased `f`
+    pub fn column(&self) -> &'static str {
+        match self {
+            PlaceLevel::Country => "f.country",
+            PlaceLevel::Region => "f.region",
+            PlaceLevel::City => "f.city",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PlaceName {
+    pub level: PlaceLevel,
+    pub name: String,
+}
+
+impl PlaceName {
+    /// Name indented by its level, for showing places as a tree in a select.
+    pub fn render_tree(&self) -> String {
+        match self.level {
+            PlaceLevel::Country => self.name.clone(),
+            PlaceLevel::Region => format!("└ {}", self.name),
+            PlaceLevel::City => format!("\u{a0}\u{a0}└ {}", self.name),
+        }
+    }
+}
+
+/// Countries each followed by their regions and cities, from sorted
+/// `(country, region, city)` rows. A name is only listed once per level.
+pub fn place_tree(rows: impl IntoIterator<Item = (String, String, String)>) -> Vec<PlaceName> {
+    let mut seen = HashSet::new();
+    let mut places = Vec::new();
+
+    for (country, region, city) in rows {
+        for (level, name) in [
+            (PlaceLevel::Country, country),
+            (PlaceLevel::Region, region),
+            (PlaceLevel::City, city),
+        ] {
+            let place = PlaceName { level, name };
+            if seen.insert(place.clone()) {
+                places.push(place);
+            }
+        }
+    }
+
+    places
+}
+
+/// Places with a grid index of one degree cells for nearest lookups.
+#[derive(Clone, Debug, Default)]
+pub struct Gazetteer {
+    places: Vec<Place>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+fn cell(lat: f64, lon: f64) -> (i32, i32) {
+    (lat.floor() as i32, lon.floor().rem_euclid(360.0) as i32)
+}
+
+//Equirectangular distance, longitude wraps around the antimeridian
+fn distance_km(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let dlat = a.0 - b.0;
+    let dlon = (a.1 - b.1 + 540.0).rem_euclid(360.0) - 180.0;
+    let cos = ((a.0 + b.0) / 2.0).to_radians().cos();
+    KM_PER_DEGREE * (dlat * dlat + (dlon * cos) * (dlon * cos)).sqrt()
+}
+
+impl Gazetteer {
+    pub fn new(places: Vec<Place>) -> Self {
+        let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (i, place) in places.iter().enumerate() {
+            cells.entry(cell(place.lat, place.lon)).or_default().push(i);
+        }
+        Gazetteer { places, cells }
+    }
+
+    /// Parse tab separated `city, latitude, longitude, region, country` lines.
+    /// Empty lines and lines starting with `#` are skipped.
+    pub fn parse(data: &str) -> Result<Self, GazetteerError> {
+        let mut places = Vec::new();
+
+        for (i, line) in data.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| GazetteerError::Line {
+                line: i + 1,
+                message: message.to_string(),
+            };
+
+            let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let [city, lat, lon, region, country] = fields[..] else {
+                return Err(error("expected 5 tab separated fields"));
+            };
+            let lat: f64 = lat.parse().map_err(|_| error("invalid latitude"))?;
+            let lon: f64 = lon.parse().map_err(|_| error("invalid longitude"))?;
+            if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                return Err(error("coordinates out of range"));
+            }
+
+            places.push(Place {
+                city: city.to_string(),
+                region: region.to_string(),
+                country: country.to_string(),
+                lat,
+                lon,
+            });
+        }
+
+        Ok(Gazetteer::new(places))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GazetteerError> {
+        Gazetteer::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.places.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.places.is_empty()
+    }
+
+    /// Closest place within `MAX_DISTANCE_KM`.
+    pub fn nearest(&self, lat: f64, lon: f64) -> Option<&Place> {
+        let dlat = (MAX_DISTANCE_KM / KM_PER_DEGREE).ceil() as i32;
+        let cos = lat.to_radians().cos();
+        let dlon = if cos * 180.0 > dlat as f64 {
+            ((dlat as f64 / cos).ceil() as i32).min(180)
+        } else {
+            180
+        };
+
+        let (lat_cell, lon_cell) = cell(lat, lon);
+        let mut best: Option<(f64, &Place)> = None;
+        for y in (lat_cell - dlat)..=(lat_cell + dlat) {
+            for x in (lon_cell - dlon)..=(lon_cell + dlon) {
+                let Some(indices) = self.cells.get(&(y, x.rem_euclid(360))) else {
+                    continue;
+                };
+                for place in indices.iter().map(|&i| &self.places[i]) {
+                    let distance = distance_km((lat, lon), (place.lat, place.lon));
+                    if distance <= MAX_DISTANCE_KM && best.is_none_or(|(d, _)| distance < d) {
+                        best = Some((distance, place));
+                    }
+                }
+            }
+        }
+
+        best.map(|(_, place)| place)
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use super::Gazetteer;
+    use sqlx::SqlitePool;
+
+    /// Store the place name of a file from its coordinates. Files outside of
+    /// the gazetteer are left without one.
+    pub async fn geocode_file(
+        file_id: &str,
+        lat: f64,
+        lon: f64,
+        gazetteer: &Gazetteer,
+        pool: &SqlitePool,
+    ) -> Result<(), sqlx::Error> {
+        let Some(place) = gazetteer.nearest(lat, lon) else {
+            return Ok(());
+        };
+
+        sqlx::query("UPDATE files SET city = ?, region = ?, country = ? WHERE id = ?")
+            .bind(&place.city)
+            .bind(&place.region)
+            .bind(&place.country)
+            .bind(file_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Name the places of files that have coordinates but no place yet,
+    /// such as files uploaded before the gazetteer existed.
+    pub async fn backfill(gazetteer: &Gazetteer, pool: &SqlitePool) -> Result<usize, sqlx::Error> {
+        let files: Vec<(String, f64, f64)> = sqlx::query_as(
+            "SELECT id, latitude, longitude FROM files
+            WHERE latitude IS NOT NULL AND longitude IS NOT NULL AND country IS NULL",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        for (id, lat, lon) in &files {
+            geocode_file(id, *lat, *lon, gazetteer, pool).await?;
+        }
+
+        Ok(files.len())
+    }
+}
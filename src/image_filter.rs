@@ -1,58 +1,63 @@
 #[cfg(feature = "ssr")]
 pub mod image_filter {
+    use crate::components::home_page::{FeedOrder, Filters};
+    use crate::components::tags;
+    use crate::filter_query::{self, ParseError};
+    use sqlx::SqlitePool;
+
     pub async fn prepare_filtered_query(
-        tag_filter: Option<(String, Vec<String>)>,
-        people_filter: Option<(String, Vec<i64>)>,
-    ) -> (Vec<String>, Vec<String>, Vec<String>) {
+        filter: &Filters,
+    ) -> Result<(Vec<String>, Vec<String>, Vec<String>), ParseError> {
         let mut conditions: Vec<String> = Vec::new();
         let mut joins: Vec<String> = Vec::new();
         let mut binds: Vec<String> = Vec::new();
 
-        if let Some((filter_type, tags)) = &tag_filter {
-            if !tags.is_empty() {
-                let valid_tags: Vec<String> = tags
+        if let Some((filter_type, tag_list)) = &filter.tags {
+            if !tag_list.is_empty() {
+                let valid_tags: Vec<String> = tag_list
                     .iter()
-                    .filter_map(|tag| {
-                        let trimmed_tag = tag.trim().to_lowercase();
-                        let is_valid = !trimmed_tag.is_empty()
-                            && trimmed_tag.chars().all(|c| {
-                                c.is_alphabetic() || c.is_numeric() || c == '-' || c == '_'
-                            });
-                        if is_valid {
-                            Some(trimmed_tag)
-                        } else {
-                            None
-                        }
-                    })
+                    .filter_map(|tag| tags::normalize_tag(tag))
                     .collect();
 
                 if !valid_tags.is_empty() {
-                    joins.push("LEFT JOIN tagFile tf ON f.id = tf.fileID LEFT JOIN tags t ON tf.tagString = t.tagString".to_string());
+                    // A parent tag matches all of its descendants
+                    let subtrees = valid_tags
+                        .iter()
+                        .map(|_| tags::subtree_condition("tf.tagString"))
+                        .collect::<Vec<_>>()
+                        .join(" OR ");
+                    let subtree_binds: Vec<String> = valid_tags
+                        .iter()
+                        .flat_map(|tag| tags::subtree_binds(tag))
+                        .collect();
 
                     match filter_type.as_str() {
                         "HAS" => {
-                            conditions.push(format!(
-                                "t.tagString IN ({})",
-                                valid_tags.iter().map(|_| "?").collect::<Vec<_>>().join(",")
-                            ));
-                            binds.extend(valid_tags);
+                            joins.push("LEFT JOIN tagFile tf ON f.id = tf.fileID".to_string());
+                            conditions.push(format!("({})", subtrees));
+                            binds.extend(subtree_binds);
                         }
                         "NOT" => {
                             conditions.push(format!(
-                                "f.id NOT IN (SELECT tf.fileID FROM tagFile tf WHERE tf.tagString IN ({}))",
-                                valid_tags.iter().map(|_| "?").collect::<Vec<_>>().join(",")
+                                "f.id NOT IN (SELECT tf.fileID FROM tagFile tf WHERE {})",
+                                subtrees
                             ));
-                            binds.extend(valid_tags);
+                            binds.extend(subtree_binds);
                         }
                         "ONLY" => {
-                            let num_tags = valid_tags.len();
+                            // Every chosen tag is present and no tag outside of them
+                            for tag in &valid_tags {
+                                conditions.push(format!(
+                                    "EXISTS (SELECT 1 FROM tagFile tf WHERE tf.fileID = f.id AND {})",
+                                    tags::subtree_condition("tf.tagString")
+                                ));
+                                binds.extend(tags::subtree_binds(tag));
+                            }
                             conditions.push(format!(
-                                "(SELECT COUNT(DISTINCT tf.tagString) FROM tagFile tf WHERE tf.fileID = f.id) = {} AND NOT EXISTS (SELECT 1 FROM tagFile tf WHERE tf.fileID = f.id AND tf.tagString NOT IN ({}))",
-                                num_tags,
-                                valid_tags.iter().map(|_| "?").collect::<Vec<_>>().join(",")
+                                "NOT EXISTS (SELECT 1 FROM tagFile tf WHERE tf.fileID = f.id AND NOT ({}))",
+                                subtrees
                             ));
-                            binds.extend(valid_tags.clone());
-                            binds.extend(valid_tags);
+                            binds.extend(subtree_binds);
                         }
                         _ => {}
                     }
@@ -60,7 +65,7 @@ pub mod image_filter {
             }
         }
 
-        if let Some((filter_type, user_ids)) = &people_filter {
+        if let Some((filter_type, user_ids)) = &filter.people {
             if !user_ids.is_empty() {
                 let valid_ids: Vec<i64> = user_ids
                     .iter()
@@ -73,25 +78,21 @@ pub mod image_filter {
                     match filter_type.as_str() {
                         "HAS" => {
                             conditions.push(format!(
-                                "uf.userID IN ({})",
-                                valid_ids
-                                    .iter()
-                                    .map(|id| id.to_string())
-                                    .collect::<Vec<_>>()
-                                    .join(",")
+                                "uf.personID IN ({})",
+                                valid_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",")
                             ));
                             binds.extend(valid_ids.iter().map(|id| id.to_string()));
                         }
                         "NOT" => {
                             conditions.push(format!(
-                                "f.id NOT IN (SELECT uf.fileID FROM userFile uf WHERE uf.userID IN ({}))",                                valid_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",")
+                                "f.id NOT IN (SELECT uf.fileID FROM userFile uf WHERE uf.personID IN ({}))",                                valid_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",")
                             ));
                             binds.extend(valid_ids.iter().map(|id| id.to_string()));
                         }
                         "ONLY" => {
                             let num_people = valid_ids.len();
                             conditions.push(format!(
-                                "(SELECT COUNT(DISTINCT uf.userID) FROM userFile uf WHERE uf.fileID = f.id) = {} AND NOT EXISTS (SELECT 1 FROM userFile uf WHERE uf.fileID = f.id AND uf.userID NOT IN ({}))",
+                                "(SELECT COUNT(DISTINCT uf.personID) FROM userFile uf WHERE uf.fileID = f.id) = {} AND NOT EXISTS (SELECT 1 FROM userFile uf WHERE uf.fileID = f.id AND uf.personID NOT IN ({}))",
                                 num_people,
                                 valid_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",")
                             ));
@@ -104,21 +105,330 @@ pub mod image_filter {
             }
         }
 
-        (conditions, joins, binds)
+        if let Some((filter_type, user_ids)) = &filter.uploader {
+            if !user_ids.is_empty() {
+                let placeholders = user_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+
+                // A photo has a single uploader, so ONLY is the same as HAS
+                match filter_type.as_str() {
+                    "HAS" | "ONLY" => {
+                        conditions.push(format!("f.uploadedBy IN ({})", placeholders));
+                        binds.extend(user_ids.iter().map(|id| id.to_string()));
+                    }
+                    "NOT" => {
+                        conditions.push(format!("f.uploadedBy NOT IN ({})", placeholders));
+                        binds.extend(user_ids.iter().map(|id| id.to_string()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some((filter_type, places)) = &filter.places {
+            if !places.is_empty() {
+                // IS so that photos without a place are never NULL
+                let matches = places
+                    .iter()
+                    .map(|place| format!("{} IS ?", place.level.column()))
+                    .collect::<Vec<_>>()
+                    .join(" OR ");
+
+                // A photo is taken in a single place, so ONLY is the same as HAS
+                match filter_type.as_str() {
+                    "HAS" | "ONLY" => {
+                        conditions.push(format!("({})", matches));
+                        binds.extend(places.iter().map(|place| place.name.clone()));
+                    }
+                    "NOT" => {
+                        conditions.push(format!("NOT ({})", matches));
+                        binds.extend(places.iter().map(|place| place.name.clone()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(album_id) = &filter.album {
+            conditions.push(
+                "f.id IN (SELECT af.fileID FROM albumFile af WHERE af.albumID = ?)".to_string(),
+            );
+            binds.push(album_id.clone());
+        }
+
+        // Date range on the capture date, undated photos are left out
+        if let Some(from) = filter.from.as_ref().filter(|date| is_date(date)) {
+            conditions.push("date(f.createdDate) >= date(?)".to_string());
+            binds.push(from.clone());
+        }
+        if let Some(to) = filter.to.as_ref().filter(|date| is_date(date)) {
+            conditions.push("date(f.createdDate) <= date(?)".to_string());
+            binds.push(to.clone());
+        }
+
+        if let Some(search) = filter.search.as_deref().and_then(fts_query) {
+            conditions.push(
+                "f.id IN (SELECT fileID FROM filesSearch WHERE filesSearch MATCH ?)".to_string(),
+            );
+            binds.push(search);
+        }
+
+        if let Some(geo) = &filter.geo {
+            let (condition, geo_binds) = geo.to_sql();
+            conditions.push(condition);
+            binds.extend(geo_binds);
+        }
+
+        if let Some(query) = filter.query.as_deref().filter(|q| !q.trim().is_empty()) {
+            let expr = filter_query::parse(query)?;
+            conditions.push(expr.to_sql(&mut binds));
+        }
+
+        Ok((conditions, joins, binds))
+    }
+
+    /// Turn free text into an FTS5 query matching every word as a prefix,
+    /// so user input can never be a syntax error.
+    pub fn fts_query(search: &str) -> Option<String> {
+        let words: Vec<String> = search
+            .split_whitespace()
+            .map(|word| word.replace('"', ""))
+            .filter(|word| !word.is_empty())
+            .map(|word| format!("\"{}\"*", word))
+            .collect();
+
+        if words.is_empty() {
+            None
+        } else {
+            Some(words.join(" "))
+        }
+    }
+
+    // YYYY-MM-DD as sent by date inputs
+    fn is_date(date: &str) -> bool {
+        date.len() == 10
+            && date.char_indices().all(|(i, c)| {
+                if i == 4 || i == 7 {
+                    c == '-'
+                } else {
+                    c.is_ascii_digit()
+                }
+            })
+    }
+
+    /// Expression the feed is sorted on, always descending
+    pub struct SortKey {
+        pub expr: &'static str,
+        /// Compared as a number. The expression must give a REAL.
+        pub numeric: bool,
+    }
+
+    /// How the feed is sorted. The file id breaks ties, so every photo has a fixed
+    /// place in the feed, which keyset pagination relies on.
+    pub struct FeedSort {
+        /// Date the feed is grouped by, `None` for no date headers
+        pub date_column: Option<&'static str>,
+        pub keys: Vec<SortKey>,
+        /// Binds of the key expressions, in order
+        pub binds: Vec<String>,
+    }
+
+    impl FeedSort {
+        /// ORDER BY clause, followed by `binds`
+        pub fn order_by(&self) -> String {
+            self.keys
+                .iter()
+                .map(|key| format!("{} DESC, ", key.expr))
+                .collect::<String>()
+                + "f.id DESC"
+        }
+
+        /// Condition for photos after a position in the feed, followed by `binds` and
+        /// then the key values and file id of the position.
+        pub fn after(&self) -> String {
+            self.compare("<")
+        }
+
+        /// Condition for photos before a position in the feed, with the same binds as `after`.
+        pub fn before(&self) -> String {
+            self.compare(">")
+        }
+
+        fn compare(&self, op: &str) -> String {
+            let keys = self
+                .keys
+                .iter()
+                .map(|key| format!("{}, ", key.expr))
+                .collect::<String>();
+            let values = self
+                .keys
+                .iter()
+                .map(|key| {
+                    if key.numeric {
+                        "CAST(? AS REAL), "
+                    } else {
+                        "?, "
+                    }
+                })
+                .collect::<String>();
+
+            format!("({}f.id) {} ({}?)", keys, op, values)
+        }
+
+        /// ORDER BY clause walking the feed backwards, followed by `binds`
+        pub fn reverse_order_by(&self) -> String {
+            self.keys
+                .iter()
+                .map(|key| format!("{} ASC, ", key.expr))
+                .collect::<String>()
+                + "f.id ASC"
+        }
+
+        /// Key values of a file, to continue the feed from it with `after` or `before`.
+        pub async fn position(
+            &self,
+            file_id: &str,
+            pool: &SqlitePool,
+        ) -> Result<Option<Vec<String>>, sqlx::Error> {
+            use sqlx::Row;
+
+            let columns = self
+                .keys
+                .iter()
+                .enumerate()
+                .map(|(i, key)| format!("{} AS k{}", key.expr, i))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            let query = format!("SELECT {} FROM files f WHERE f.id = ?;", columns);
+            let mut query = sqlx::query(&query);
+            for bind in &self.binds {
+                query = query.bind(bind);
+            }
+            let Some(row) = query.bind(file_id).fetch_optional(pool).await? else {
+                return Ok(None);
+            };
+
+            self.keys
+                .iter()
+                .enumerate()
+                .map(|(i, key)| {
+                    let column = format!("k{}", i);
+                    if key.numeric {
+                        row.try_get::<f64, _>(column.as_str())
+                            .map(|value| value.to_string())
+                    } else {
+                        row.try_get::<String, _>(column.as_str())
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Some)
+        }
+    }
+
+    /// Search results are ranked by relevance and manual order falls back to upload date
+    /// outside of an album. Neither has date headers.
+    pub fn feed_order(filter: &Filters) -> FeedSort {
+        if let Some(search) = filter.search.as_deref().and_then(fts_query) {
+            return FeedSort {
+                date_column: None,
+                keys: vec![
+                    // Lower rank is more relevant
+                    SortKey {
+                        expr: "CAST(-(SELECT rank FROM filesSearch WHERE filesSearch MATCH ? AND fileID = f.id) AS REAL)",
+                        numeric: true,
+                    },
+                    SortKey {
+                        expr: "f.uploadDate",
+                        numeric: false,
+                    },
+                ],
+                binds: vec![search],
+            };
+        }
+
+        match (filter.order, &filter.album) {
+            (FeedOrder::Manual, Some(album_id)) => FeedSort {
+                date_column: None,
+                keys: vec![
+                    SortKey {
+                        expr: "CAST(-(SELECT af.position FROM albumFile af WHERE af.albumID = ? AND af.fileID = f.id) AS REAL)",
+                        numeric: true,
+                    },
+                    SortKey {
+                        expr: "f.uploadDate",
+                        numeric: false,
+                    },
+                ],
+                binds: vec![album_id.clone()],
+            },
+            // Undated photos go last
+            (FeedOrder::CaptureDate, _) => FeedSort {
+                date_column: Some("f.createdDate"),
+                keys: vec![
+                    SortKey {
+                        expr: "COALESCE(f.createdDate, '')",
+                        numeric: false,
+                    },
+                    SortKey {
+                        expr: "f.uploadDate",
+                        numeric: false,
+                    },
+                ],
+                binds: vec![],
+            },
+            _ => FeedSort {
+                date_column: Some("f.uploadDate"),
+                keys: vec![SortKey {
+                    expr: "f.uploadDate",
+                    numeric: false,
+                }],
+                binds: vec![],
+            },
+        }
+    }
+
+    /// Condition hiding files that belong to a private album the user is not a member of.
+    /// Uploaders can always see their own files.
+    pub fn access_condition(user_id: i64) -> (String, Vec<String>) {
+        let condition = "(f.uploadedBy = ? OR f.id NOT IN (
+                SELECT af.fileID FROM albumFile af
+                INNER JOIN albums a ON a.id = af.albumID
+                WHERE a.private = 1 AND a.ownerId != ? AND NOT EXISTS (
+                    SELECT 1 FROM albumAccess aa WHERE aa.albumID = a.id AND aa.userID = ?)))"
+            .to_string();
+
+        (condition, vec![user_id.to_string(); 3])
+    }
+
+    pub async fn can_view_file(
+        file_id: &str,
+        user_id: i64,
+        pool: &SqlitePool,
+    ) -> Result<bool, sqlx::Error> {
+        let (condition, binds) = access_condition(user_id);
+        let query = format!(
+            "SELECT EXISTS(SELECT 1 FROM files f WHERE f.id = ? AND {});",
+            condition
+        );
+
+        let mut query = sqlx::query_scalar(&query).bind(file_id);
+        for bind in binds {
+            query = query.bind(bind);
+        }
+
+        query.fetch_one(pool).await
     }
 
     pub async fn prepare_filtered_query_with_pagination(
-        tag_filter: Option<(String, Vec<String>)>,
-        people_filter: Option<(String, Vec<i64>)>,
+        filter: &Filters,
         limit: usize,
         offset: usize,
-    ) -> (Vec<String>, Vec<String>, Vec<String>) {
-        let (conditions, joins, mut binds) =
-            prepare_filtered_query(tag_filter, people_filter).await;
+    ) -> Result<(Vec<String>, Vec<String>, Vec<String>), ParseError> {
+        let (conditions, joins, mut binds) = prepare_filtered_query(filter).await?;
         binds.push(limit.to_string());
         binds.push(offset.to_string());
 
-        (conditions, joins, binds)
+        Ok((conditions, joins, binds))
     }
 
     pub fn build_filtered_query(
@@ -4,7 +4,12 @@ pub mod components;
 pub mod db;
 pub mod error_template;
 #[cfg(feature = "ssr")]
+pub mod face_detector;
+pub mod face_embedding;
+#[cfg(feature = "ssr")]
 pub mod fileserv;
+pub mod filter_query;
+pub mod gazetteer;
 pub mod image_filter;
 pub mod session;
 #[cfg(feature = "ssr")]
@@ -17,10 +17,15 @@ use leptos_axum::{generate_route_list, handle_server_fns_with_context, LeptosRou
 use photo_album::{
     app::*,
     auth::ssr::{AuthSession, Backend},
+    components::faces::ssr::{ClusterCache, FaceScan},
+    face_detector::{DetectorConfig, FaceDetectors},
+    face_embedding::ssr::FaceEmbedder,
+    gazetteer::Gazetteer,
     session::session_expiry::SessionExpiryConfig,
     state::AppState,
 };
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use std::sync::Arc;
 use tokio::{signal, task::AbortHandle};
 use tower_sessions_sqlx_store::SqliteStore;
 
@@ -39,6 +44,10 @@ async fn server_fn_handler(
             provide_context(session.clone());
             provide_context(app_state.expiry_config.clone());
             provide_context(app_state.pool.clone());
+            provide_context(app_state.gazetteer.clone());
+            provide_context(app_state.face_detectors.clone());
+            provide_context(app_state.face_scan.clone());
+            provide_context(app_state.face_clusters.clone());
         },
         request,
     )
@@ -137,6 +146,42 @@ async fn main() {
     //initalize first admin onfirst run
     add_first_user("admin".to_string(), &pool).await;
 
+    // Place names for photo coordinates, the app works without them
+    let gazetteer = match Gazetteer::load("gazetteer.tsv") {
+        Ok(gazetteer) => gazetteer,
+        Err(e) => {
+            eprintln!("{e}");
+            Gazetteer::default()
+        }
+    };
+    match photo_album::gazetteer::ssr::backfill(&gazetteer, &pool).await {
+        Ok(0) => {}
+        Ok(n) => log!("Named the place of {} photos", n),
+        Err(e) => eprintln!("{e:?}"),
+    }
+    let gazetteer = Arc::new(gazetteer);
+
+    // Face detectors are loaded once and shared between requests
+    let detector_config = DetectorConfig::from_env().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        DetectorConfig::default()
+    });
+    let mut face_detectors =
+        FaceDetectors::load("model.bin", detector_config).expect("Could not load face detectors.");
+
+    // People are only suggested for faces when a face recognition model is present
+    let embedding_model =
+        std::env::var("FACE_EMBEDDING_MODEL").unwrap_or("face_embedding.onnx".to_string());
+    if fs::metadata(&embedding_model).is_ok() {
+        match FaceEmbedder::load(&embedding_model) {
+            Ok(embedder) => face_detectors = face_detectors.with_embedder(embedder),
+            Err(e) => eprintln!("Could not load {}: {}", embedding_model, e),
+        }
+    } else {
+        log!("No {}, face suggestions are off", embedding_model);
+    }
+    let face_detectors = Arc::new(face_detectors);
+
     // Setting this to None means we'll be using cargo-leptos and its env vars
     let conf = get_configuration(None).await.unwrap();
     let leptos_options = conf.leptos_options;
@@ -149,6 +194,10 @@ async fn main() {
         expiry_config,
         pool: pool.clone(),
         routes: routes.clone(),
+        gazetteer,
+        face_detectors,
+        face_scan: Arc::new(FaceScan::default()),
+        face_clusters: Arc::new(ClusterCache::default()),
     };
 
     // build our application with a route
@@ -1,8 +1,12 @@
+use crate::components::faces::ssr::{ClusterCache, FaceScan};
+use crate::face_detector::FaceDetectors;
+use crate::gazetteer::Gazetteer;
 use crate::session::session_expiry::SessionExpiryConfig;
 use axum::extract::FromRef;
 use leptos::LeptosOptions;
 use leptos_router::RouteListing;
 use sqlx::SqlitePool;
+use std::sync::Arc;
 
 /// This takes advantage of Axum's SubStates feature by deriving FromRef. This is the only way to have more than one
 /// item in Axum's State. Leptos requires you to have leptosOptions in your State struct for the leptos route handlers
@@ -12,4 +16,8 @@ pub struct AppState {
     pub expiry_config: SessionExpiryConfig,
     pub pool: SqlitePool,
     pub routes: Vec<RouteListing>,
+    pub gazetteer: Arc<Gazetteer>,
+    pub face_detectors: Arc<FaceDetectors>,
+    pub face_scan: Arc<FaceScan>,
+    pub face_clusters: Arc<ClusterCache>,
 }
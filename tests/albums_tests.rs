@@ -0,0 +1,80 @@
+use photo_album::auth::User;
+use photo_album::components::albums::ssr::{
+    remove_file, require_can_hide, require_can_make_private,
+};
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Executor, SqlitePool};
+
+async fn database() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Could not make pool.");
+    sqlx::migrate!().run(&pool).await.unwrap();
+
+    pool.execute(
+        "INSERT INTO users (id, username, signed_up) VALUES (1, 'uploader', 1), (2, 'other', 1);
+        INSERT INTO files (id, path, uploadedBy, uploadDate) VALUES ('a', 'a.jpg', 1, '2024-06-01');
+        INSERT INTO albums (id, name, ownerId, private, createdDate) VALUES
+            ('private', 'Private', 2, 1, '2024-06-01'), ('public', 'Public', 2, 0, '2024-06-01');
+        INSERT INTO albumFile (albumID, fileID, position) VALUES ('public', 'a', 0);",
+    )
+    .await
+    .unwrap();
+
+    pool
+}
+
+fn user(id: i64, permissions: &[&str]) -> User {
+    User {
+        id,
+        permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn only_uploaders_hide_photos() {
+    let pool = database().await;
+    let other = user(2, &[]);
+
+    //Someone else's photo can not be hidden in a private album of their own
+    assert!(require_can_hide("private", "a", &other, &pool)
+        .await
+        .is_err());
+    assert!(require_can_make_private("public", &other, &pool)
+        .await
+        .is_err());
+    assert!(require_can_hide("public", "a", &other, &pool).await.is_ok());
+
+    assert!(require_can_hide("private", "a", &user(1, &[]), &pool)
+        .await
+        .is_ok());
+    let admin = user(2, &["admin"]);
+    assert!(require_can_hide("private", "a", &admin, &pool)
+        .await
+        .is_ok());
+    assert!(require_can_make_private("public", &admin, &pool)
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn removing_the_cover_clears_it() {
+    let pool = database().await;
+    pool.execute("UPDATE albums SET coverID = 'a' WHERE id = 'public'")
+        .await
+        .unwrap();
+
+    let mut tx = pool.begin().await.unwrap();
+    remove_file("public", "a", &mut tx).await.unwrap();
+    tx.commit().await.unwrap();
+
+    let cover: Option<String> =
+        sqlx::query_scalar("SELECT coverID FROM albums WHERE id = 'public'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(cover, None);
+}
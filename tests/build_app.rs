@@ -16,10 +16,14 @@ use leptos_axum::{generate_route_list, handle_server_fns_with_context, LeptosRou
 use photo_album::{
     app::*,
     auth::ssr::{AuthSession, Backend},
+    components::faces::ssr::{ClusterCache, FaceScan},
+    face_detector::{DetectorConfig, FaceDetectors},
+    gazetteer::Gazetteer,
     session::session_expiry::SessionExpiryConfig,
     state::AppState,
 };
 use sqlx::sqlite::SqlitePoolOptions;
+use std::sync::Arc;
 use tower_sessions_sqlx_store::SqliteStore;
 
 async fn server_fn_handler(
@@ -37,6 +41,10 @@ async fn server_fn_handler(
             provide_context(session.clone());
             provide_context(app_state.expiry_config.clone());
             provide_context(app_state.pool.clone());
+            provide_context(app_state.gazetteer.clone());
+            provide_context(app_state.face_detectors.clone());
+            provide_context(app_state.face_scan.clone());
+            provide_context(app_state.face_clusters.clone());
         },
         request,
     )
@@ -77,12 +85,19 @@ pub async fn build_app() -> Router {
     let leptos_options = conf.leptos_options;
     let expiry_config: SessionExpiryConfig = Default::default();
     let routes = generate_route_list(App);
+    let gazetteer = Arc::new(Gazetteer::load("gazetteer.tsv").unwrap_or_default());
+    let face_detectors =
+        Arc::new(FaceDetectors::load("model.bin", DetectorConfig::default()).unwrap());
 
     let app_state = AppState {
         leptos_options,
         expiry_config,
         pool: pool.clone(),
         routes: routes.clone(),
+        gazetteer,
+        face_detectors,
+        face_scan: Arc::new(FaceScan::default()),
+        face_clusters: Arc::new(ClusterCache::default()),
     };
 
     // build our application with a route
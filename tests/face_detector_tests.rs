@@ -0,0 +1,51 @@
+use photo_album::face_detector::DetectorConfig;
+use std::collections::HashMap;
+
+fn config(vars: &[(&str, &str)]) -> Result<DetectorConfig, String> {
+    let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+    DetectorConfig::from_vars(|name| vars.get(name).map(|v| v.to_string()))
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn reads_detector_settings() {
+    assert_eq!(config(&[]).unwrap(), DetectorConfig::default());
+
+    let custom = config(&[
+        ("FACE_MIN_SIZE", "40"),
+        ("FACE_SCORE_THRESHOLD", "1.5"),
+        ("FACE_PYRAMID_SCALE", "0.5"),
+        ("FACE_WINDOW_STEP", "2,3"),
+        ("FACE_DETECTORS", "2"),
+        ("FACE_MATCH_THRESHOLD", "0.7"),
+    ])
+    .unwrap();
+    assert_eq!(
+        custom,
+        DetectorConfig {
+            min_face_size: 40,
+            score_thresh: 1.5,
+            pyramid_scale_factor: 0.5,
+            slide_window_step: (2, 3),
+            detectors: 2,
+            match_threshold: 0.7,
+        }
+    );
+
+    let square = config(&[("FACE_WINDOW_STEP", " 8 ")]).unwrap();
+    assert_eq!(square.slide_window_step, (8, 8));
+}
+
+#[test]
+fn rejects_invalid_detector_settings() {
+    assert_eq!(
+        config(&[("FACE_MIN_SIZE", "10")]).unwrap_err(),
+        "Invalid face detector setting FACE_MIN_SIZE=10"
+    );
+    assert!(config(&[("FACE_PYRAMID_SCALE", "1.2")]).is_err());
+    assert!(config(&[("FACE_WINDOW_STEP", "4,0")]).is_err());
+    assert!(config(&[("FACE_WINDOW_STEP", "1,2,3")]).is_err());
+    assert!(config(&[("FACE_DETECTORS", "0")]).is_err());
+    assert!(config(&[("FACE_SCORE_THRESHOLD", "high")]).is_err());
+    assert!(config(&[("FACE_MATCH_THRESHOLD", "1.5")]).is_err());
+}
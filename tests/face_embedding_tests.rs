@@ -0,0 +1,70 @@
+use photo_album::face_embedding::{
+    cluster_faces, cosine_similarity, from_blob, nearest_person, normalize, to_blob,
+};
+
+#[test]
+fn stores_embeddings_as_blobs() {
+    let embedding = vec![0.5, -1.25, 3.0];
+    let blob = to_blob(&embedding);
+    assert_eq!(blob.len(), 12);
+    assert_eq!(from_blob(&blob), Some(embedding));
+
+    assert_eq!(from_blob(&[]), None);
+    assert_eq!(from_blob(&[1, 2, 3]), None);
+}
+
+#[test]
+fn compares_embeddings() {
+    let mut a = vec![3.0, 4.0];
+    normalize(&mut a);
+    assert_eq!(a, vec![0.6, 0.8]);
+
+    assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+    assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+    assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0]), 0.0);
+    assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+}
+
+#[test]
+fn suggests_nearest_person() {
+    let known = vec![
+        (1, "Kari".to_string(), vec![1.0, 0.0]),
+        (2, "Ola".to_string(), vec![0.0, 1.0]),
+        (1, "Kari".to_string(), vec![0.8, 0.6]),
+    ];
+
+    let suggestion = nearest_person(&[0.9, 0.5], &known, 0.5).unwrap();
+    assert_eq!(suggestion.person_id, 1);
+    assert_eq!(suggestion.name, "Kari");
+    assert!(suggestion.similarity > 0.95);
+
+    assert_eq!(
+        nearest_person(&[0.1, 1.0], &known, 0.5).unwrap().name,
+        "Ola"
+    );
+    assert_eq!(nearest_person(&[-1.0, -1.0], &known, 0.5), None);
+    assert_eq!(nearest_person(&[1.0, 0.0], &[], 0.0), None);
+}
+
+#[test]
+fn clusters_similar_faces() {
+    let faces = vec![
+        (1, vec![1.0, 0.0]),
+        (2, vec![0.0, 1.0]),
+        (3, vec![0.95, 0.1]),
+        (4, vec![0.9, -0.1]),
+        (5, vec![0.1, 0.9]),
+        (6, vec![-1.0, 0.0]),
+    ];
+
+    let groups = cluster_faces(&faces, 0.8);
+    let ids: Vec<Vec<i64>> = groups.iter().map(|g| g.face_ids.clone()).collect();
+    assert_eq!(ids, vec![vec![1, 3, 4], vec![2, 5], vec![6]]);
+
+    let length: f32 = groups[0].centroid.iter().map(|v| v * v).sum();
+    assert!((length - 1.0).abs() < 1e-5);
+    assert!(groups[0].centroid[0] > 0.99);
+
+    assert!(cluster_faces(&[], 0.8).is_empty());
+    assert_eq!(cluster_faces(&faces, 1.1).len(), faces.len());
+}
@@ -0,0 +1,142 @@
+use photo_album::filter_query::{parse, Expr, Term, MAX_DEPTH, MAX_TERMS};
+use photo_album::gazetteer::PlaceLevel;
+
+fn tag(name: &str) -> Expr {
+    Expr::Term(Term::Tag(name.to_string()))
+}
+
+fn person(name: &str) -> Expr {
+    Expr::Term(Term::Person(name.to_string()))
+}
+
+#[test]
+fn parses_single_term() {
+    assert_eq!(parse("tag:Beach").unwrap(), tag("beach"));
+    assert_eq!(parse("tag:Travel/Norway").unwrap(), tag("travel/norway"));
+    assert_eq!(
+        parse("uploader:Dad").unwrap(),
+        Expr::Term(Term::Uploader("Dad".to_string()))
+    );
+    assert_eq!(
+        parse("person:\"Anna Smith\"").unwrap(),
+        person("Anna Smith")
+    );
+}
+
+#[test]
+fn and_binds_tighter_than_or() {
+    assert_eq!(
+        parse("tag:a OR tag:b AND tag:c").unwrap(),
+        Expr::Or(
+            Box::new(tag("a")),
+            Box::new(Expr::And(Box::new(tag("b")), Box::new(tag("c"))))
+        )
+    );
+}
+
+#[test]
+fn adjacent_terms_are_and_ed() {
+    assert_eq!(
+        parse("tag:a tag:b").unwrap(),
+        parse("tag:a AND tag:b").unwrap()
+    );
+}
+
+#[test]
+fn parses_full_expression() {
+    let expr =
+        parse("person:anna AND (tag:beach OR tag:lake) AND NOT tag:work AND year:2019..2021")
+            .unwrap();
+    assert_eq!(
+        expr,
+        Expr::And(
+            Box::new(Expr::And(
+                Box::new(Expr::And(
+                    Box::new(person("anna")),
+                    Box::new(Expr::Or(Box::new(tag("beach")), Box::new(tag("lake"))))
+                )),
+                Box::new(Expr::Not(Box::new(tag("work"))))
+            )),
+            Box::new(Expr::Term(Term::Year(2019, 2021)))
+        )
+    );
+
+    let mut binds = Vec::new();
+    let sql = expr.to_sql(&mut binds);
+    assert_eq!(
+        binds,
+        vec![
+            "anna", "beach", "beach/", "beach0", "lake", "lake/", "lake0", "work", "work/",
+            "work0", "2019", "2021"
+        ]
+    );
+    assert_eq!(sql.matches('?').count(), binds.len());
+}
+
+#[test]
+fn parses_place_terms() {
+    let expr = parse("country:norway AND NOT city:\"Mo i Rana\"").unwrap();
+    assert_eq!(
+        expr,
+        Expr::And(
+            Box::new(Expr::Term(Term::Place(
+                PlaceLevel::Country,
+                "norway".to_string()
+            ))),
+            Box::new(Expr::Not(Box::new(Expr::Term(Term::Place(
+                PlaceLevel::City,
+                "Mo i Rana".to_string()
+            ))))),
+        )
+    );
+
+    let mut binds = Vec::new();
+    let sql = expr.to_sql(&mut binds);
+    assert_eq!(binds, vec!["norway", "Mo i Rana"]);
+    assert!(sql.contains("f.country = ? COLLATE NOCASE"));
+}
+
+#[test]
+fn values_are_never_inlined() {
+    let mut binds = Vec::new();
+    let sql = parse("person:\"x' OR 1=1 --\"").unwrap().to_sql(&mut binds);
+    assert!(!sql.contains("1=1"));
+    assert_eq!(binds, vec!["x' OR 1=1 --"]);
+
+    assert!(parse("tag:\"x' OR 1=1 --\"").is_err());
+}
+
+#[test]
+fn reports_error_positions() {
+    let err = parse("tag:a AND (tag:b OR tag:c").unwrap_err();
+    assert_eq!(err.position, 25);
+    assert!(err.message.contains("Missing ')'"));
+
+    let err = parse("tag:a beach").unwrap_err();
+    assert_eq!(err.position, 6);
+
+    let err = parse("colour:red").unwrap_err();
+    assert!(err.message.contains("Unknown field"));
+
+    let err = parse("year:2021..2019").unwrap_err();
+    assert!(err.message.contains("reversed"));
+
+    let err = parse("tag:a AND").unwrap_err();
+    assert_eq!(err.position, 9);
+
+    assert!(parse("tag:\"open").is_err());
+    assert!(parse("tag:a )").is_err());
+}
+
+#[test]
+fn limits_nesting_and_length() {
+    let nested = |depth: usize| format!("{}tag:a{}", "(".repeat(depth), ")".repeat(depth));
+    assert!(parse(&nested(MAX_DEPTH)).is_ok());
+    assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+    assert!(parse(&nested(100_000)).is_err());
+    assert!(parse(&format!("{}tag:a", "NOT ".repeat(100_000))).is_err());
+
+    let terms = |count: usize| vec!["tag:a"; count].join(" OR ");
+    assert!(parse(&terms(MAX_TERMS)).is_ok());
+    assert!(parse(&terms(MAX_TERMS + 1)).is_err());
+}
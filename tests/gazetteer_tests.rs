@@ -0,0 +1,64 @@
+use photo_album::gazetteer::{place_tree, Gazetteer, PlaceLevel, PlaceName};
+
+const PLACES: &str = "# city, latitude, longitude, region, country
+Oslo\t59.9139\t10.7522\tOslo\tNorway
+Drammen\t59.7439\t10.2045\tBuskerud\tNorway
+Bergen\t60.3913\t5.3221\tVestland\tNorway
+
+Waiyevo\t-16.7900\t-179.9800\tNorthern\tFiji
+";
+
+#[test]
+fn parses_places() {
+    let gazetteer = Gazetteer::parse(PLACES).unwrap();
+    assert_eq!(gazetteer.len(), 4);
+
+    let error = Gazetteer::parse("Oslo\t59.9\tnorth\tOslo\tNorway")
+        .unwrap_err()
+        .to_string();
+    assert_eq!(error, "Gazetteer line 1: invalid longitude");
+    assert!(Gazetteer::parse("Oslo\t59.9\t10.7").is_err());
+    assert!(Gazetteer::parse("Nowhere\t95.0\t10.7\tX\tY").is_err());
+}
+
+#[test]
+fn finds_nearest_place() {
+    let gazetteer = Gazetteer::parse(PLACES).unwrap();
+
+    let city = |lat, lon| gazetteer.nearest(lat, lon).map(|place| place.city.as_str());
+    // Holmenkollen
+    assert_eq!(city(59.9633, 10.6678), Some("Oslo"));
+    // Hokksund is closer to Drammen
+    assert_eq!(city(59.7701, 9.9100), Some("Drammen"));
+    // Across the antimeridian
+    assert_eq!(city(-16.8, 179.9), Some("Waiyevo"));
+    // Middle of the North Sea
+    assert_eq!(city(57.0, 2.0), None);
+}
+
+#[test]
+fn builds_place_tree() {
+    let place = |level, name: &str| PlaceName {
+        level,
+        name: name.to_string(),
+    };
+    let rows = vec![
+        ("Norway", "Oslo", "Oslo"),
+        ("Norway", "Vestland", "Bergen"),
+        ("Norway", "Vestland", "Voss"),
+    ]
+    .into_iter()
+    .map(|(c, r, city)| (c.to_string(), r.to_string(), city.to_string()));
+
+    assert_eq!(
+        place_tree(rows),
+        vec![
+            place(PlaceLevel::Country, "Norway"),
+            place(PlaceLevel::Region, "Oslo"),
+            place(PlaceLevel::City, "Oslo"),
+            place(PlaceLevel::Region, "Vestland"),
+            place(PlaceLevel::City, "Bergen"),
+            place(PlaceLevel::City, "Voss"),
+        ]
+    );
+}
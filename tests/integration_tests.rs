@@ -46,7 +46,6 @@ mod api_tests {
             .post("/api/fetch_files17384435655166834659")
             .expect_failure()
             .form(&json!({
-                "db_index": "0",
                 "count": 1
             }));
 
@@ -67,7 +66,6 @@ mod api_tests {
             .expect_failure()
             .add_cookie(cookie)
             .form(&json!({
-                "db_index": "0",
                 "count": 1
             }));
 
@@ -87,7 +85,6 @@ mod api_tests {
         let req = server
             .post("/api/fetch_files17384435655166834659")
             .form(&json!({
-                "db_index": "0",
                 "count": 1
             }));
 
@@ -114,7 +111,6 @@ mod api_tests {
         let req = server
             .post("/api/fetch_files17384435655166834659")
             .form(&json!({
-                "db_index": "0",
                 "count": 1
             }));
 
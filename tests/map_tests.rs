@@ -0,0 +1,68 @@
+use photo_album::components::map::{cluster, parse_point, to_geojson, GeoFilter, GeoPoint};
+
+fn point(id: &str, lat: f64, lon: f64) -> GeoPoint {
+    GeoPoint {
+        id: id.to_string(),
+        lat,
+        lon,
+    }
+}
+
+#[test]
+fn parses_points() {
+    assert_eq!(parse_point("59.91, 10.75"), Some((59.91, 10.75)));
+    assert_eq!(parse_point("-33.9,18.4"), Some((-33.9, 18.4)));
+    assert_eq!(parse_point("91, 10"), None);
+    assert_eq!(parse_point("59.91"), None);
+    assert_eq!(parse_point("north, east"), None);
+}
+
+#[test]
+fn radius_bounds_widen_with_latitude() {
+    let (south, west, north, east) = GeoFilter::Radius {
+        lat: 60.0,
+        lon: 10.0,
+        km: 111.32,
+    }
+    .bounds();
+    assert!((south - 59.0).abs() < 1e-9 && (north - 61.0).abs() < 1e-9);
+    // A degree of longitude is half as long at 60°
+    assert!((west - 8.0).abs() < 1e-6 && (east - 12.0).abs() < 1e-6);
+
+    let (_, west, _, east) = GeoFilter::Radius {
+        lat: 89.99,
+        lon: 0.0,
+        km: 50.0,
+    }
+    .bounds();
+    assert_eq!((west, east), (-180.0, 180.0));
+}
+
+#[test]
+fn clusters_by_zoom() {
+    let points = vec![
+        point("oslo", 59.91, 10.75),
+        point("drammen", 59.74, 10.20),
+        point("bergen", 60.39, 5.32),
+    ];
+
+    let near = cluster(&points, 12);
+    assert_eq!(near.len(), 3);
+
+    let far = cluster(&points, 2);
+    assert_eq!(far.len(), 1);
+    assert_eq!(far[0].count, 3);
+    assert_eq!(far[0].photo, "oslo");
+    assert!((far[0].lat - (59.91 + 59.74 + 60.39) / 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn writes_geojson() {
+    let geojson = to_geojson(&cluster(&[point("oslo", 59.91, 10.75)], 10));
+    assert_eq!(geojson["type"], "FeatureCollection");
+    let feature = &geojson["features"][0];
+    assert_eq!(feature["geometry"]["coordinates"][0], 10.75);
+    assert_eq!(feature["geometry"]["coordinates"][1], 59.91);
+    assert_eq!(feature["properties"]["count"], 1);
+    assert_eq!(feature["properties"]["photo"], "oslo");
+}
@@ -0,0 +1,182 @@
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Executor, SqlitePool};
+use std::borrow::Cow;
+
+const PEOPLE_MIGRATION: i64 = 20240623120000;
+
+//In-memory database with the migrations before `version` applied
+async fn migrated_before(version: i64) -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Could not make pool.");
+
+    let mut migrator: Migrator = sqlx::migrate!();
+    migrator.migrations = Cow::Owned(
+        migrator
+            .migrations
+            .iter()
+            .filter(|migration| migration.version < version)
+            .cloned()
+            .collect(),
+    );
+    migrator.run(&pool).await.unwrap();
+
+    pool
+}
+
+#[tokio::test]
+async fn tagged_users_become_people() {
+    let pool = migrated_before(PEOPLE_MIGRATION).await;
+
+    pool.execute(
+        "INSERT INTO users (id, username, admin, signed_up) VALUES
+            (1, 'admin', 1, 1), (2, 'invited', 0, 0), (3, 'tagged', 0, 0), (4, 'untagged', 0, 0);
+        INSERT INTO invites (token, user_id, admin_id) VALUES ('token', 2, 1);
+        INSERT INTO files (id, path, uploadedBy, uploadDate) VALUES
+            ('a', 'a.jpg', 1, '2024-06-01'), ('b', 'b.jpg', 1, '2024-06-01');
+        INSERT INTO userFile (userID, fileID, x, y, width, height, embedding) VALUES
+            (1, 'a', 1, 2, 3, 4, X'0000803f'),
+            (3, 'a', NULL, NULL, NULL, NULL, NULL),
+            (2, 'b', 5, 6, 7, 8, X'00000040');",
+    )
+    .await
+    .unwrap();
+
+    sqlx::migrate!().run(&pool).await.unwrap();
+
+    //Only accounts that signed up or were invited stay linked
+    let people: Vec<(i64, String, Option<i64>)> =
+        sqlx::query_as("SELECT id, name, userID FROM people ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        people,
+        vec![
+            (1, "admin".to_string(), Some(1)),
+            (2, "invited".to_string(), Some(2)),
+            (3, "tagged".to_string(), None),
+        ]
+    );
+
+    //Only accounts created by tagging are removed, an account that was never
+    //tagged could still be waiting for its invite
+    let users: Vec<i64> = sqlx::query_scalar("SELECT id FROM users ORDER BY id")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(users, vec![1, 2, 4]);
+
+    type Tag = (
+        i64,
+        String,
+        Option<u32>,
+        Option<u32>,
+        Option<u32>,
+        Option<u32>,
+        Option<Vec<u8>>,
+    );
+    let tags: Vec<Tag> = sqlx::query_as(
+        "SELECT personID, fileID, x, y, width, height, embedding FROM userFile
+        ORDER BY fileID, personID",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        tags,
+        vec![
+            (
+                1,
+                "a".to_string(),
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4),
+                Some(vec![0, 0, 0x80, 0x3f])
+            ),
+            (3, "a".to_string(), None, None, None, None, None),
+            (
+                2,
+                "b".to_string(),
+                Some(5),
+                Some(6),
+                Some(7),
+                Some(8),
+                Some(vec![0, 0, 0, 0x40])
+            ),
+        ]
+    );
+
+    let search: Vec<(String, String)> =
+        sqlx::query_as("SELECT fileID, people FROM filesSearch ORDER BY fileID")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        search,
+        vec![
+            ("a".to_string(), "admin tagged".to_string()),
+            ("b".to_string(), "invited".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn merges_people() {
+    use photo_album::components::people::ssr::merge_into;
+
+    let pool = migrated_before(i64::MAX).await;
+    pool.execute(
+        "INSERT INTO users (id, username, signed_up) VALUES (1, 'ann', 1);
+        INSERT INTO people (id, name, userID, avatar) VALUES
+            (1, 'Ann', NULL, NULL), (2, 'Anne', 1, X'01');
+        INSERT INTO files (id, path, uploadedBy, uploadDate) VALUES
+            ('a', 'a.jpg', 1, '2024-06-01'), ('b', 'b.jpg', 1, '2024-06-01'),
+            ('c', 'c.jpg', 1, '2024-06-01'), ('d', 'd.jpg', 1, '2024-06-01');
+        INSERT INTO userFile (personID, fileID, x, y, width, height, embedding) VALUES
+            (1, 'a', NULL, NULL, NULL, NULL, NULL), (2, 'a', 5, 6, 7, 8, X'02'),
+            (1, 'b', 1, 2, 3, 4, X'01'), (2, 'b', 5, 6, 7, 8, X'02'),
+            (2, 'c', NULL, NULL, NULL, NULL, NULL),
+            (1, 'd', NULL, NULL, NULL, NULL, NULL);",
+    )
+    .await
+    .unwrap();
+
+    let mut tx = pool.begin().await.unwrap();
+    merge_into(2, 1, &mut tx).await.unwrap();
+    tx.commit().await.unwrap();
+
+    //The marked face is kept, and the face of the person merged into wins
+    let tags: Vec<(i64, String, Option<u32>, Option<Vec<u8>>)> =
+        sqlx::query_as("SELECT personID, fileID, x, embedding FROM userFile ORDER BY fileID")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        tags,
+        vec![
+            (1, "a".to_string(), Some(5), Some(vec![2])),
+            (1, "b".to_string(), Some(1), Some(vec![1])),
+            (1, "c".to_string(), None, None),
+            (1, "d".to_string(), None, None),
+        ]
+    );
+
+    //The account link and avatar move over
+    let people: Vec<(i64, String, Option<i64>, Option<Vec<u8>>)> =
+        sqlx::query_as("SELECT id, name, userID, avatar FROM people")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(people, vec![(1, "Ann".to_string(), Some(1), Some(vec![1]))]);
+
+    let search: Vec<String> = sqlx::query_scalar("SELECT people FROM filesSearch ORDER BY fileID")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(search, vec!["Ann", "Ann", "Ann", "Ann"]);
+}
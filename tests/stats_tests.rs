@@ -0,0 +1,9 @@
+use photo_album::components::stats::format_bytes;
+
+#[test]
+fn formats_bytes() {
+    assert_eq!(format_bytes(0), "0 B");
+    assert_eq!(format_bytes(1023), "1023 B");
+    assert_eq!(format_bytes(1536), "1.5 KB");
+    assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GB");
+}
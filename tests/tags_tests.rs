@@ -0,0 +1,53 @@
+use photo_album::components::home_page::Tag;
+use photo_album::components::tags::{ancestors, normalize_tag, sort_tree, subtree_binds};
+
+#[test]
+fn normalizes_tags() {
+    assert_eq!(
+        normalize_tag(" Travel/ Norway /Bergen "),
+        Some("travel/norway/bergen".to_string())
+    );
+    assert_eq!(
+        normalize_tag("new york//city"),
+        Some("new-york/city".to_string())
+    );
+    assert_eq!(normalize_tag("/"), None);
+    assert_eq!(normalize_tag("a;b"), None);
+}
+
+#[test]
+fn lists_ancestors() {
+    assert_eq!(
+        ancestors("travel/norway/bergen"),
+        vec!["travel", "travel/norway"]
+    );
+    assert!(ancestors("travel").is_empty());
+}
+
+#[test]
+fn subtree_range_covers_descendants_only() {
+    let [tag, low, high] = subtree_binds("travel");
+    let matches = |t: &str| t == tag || (t > low.as_str() && t < high.as_str());
+    assert!(matches("travel"));
+    assert!(matches("travel/norway"));
+    assert!(matches("travel/norway/bergen"));
+    assert!(!matches("travel-2019"));
+    assert!(!matches("travels"));
+    assert!(!matches("trave"));
+}
+
+#[test]
+fn sorts_children_after_parent() {
+    let mut tags: Vec<Tag> = ["travel/norway", "travel-2019", "travel", "beach"]
+        .iter()
+        .map(|t| Tag {
+            tag_string: t.to_string(),
+        })
+        .collect();
+    sort_tree(&mut tags);
+    let sorted: Vec<&str> = tags.iter().map(|t| t.tag_string.as_str()).collect();
+    assert_eq!(
+        sorted,
+        vec!["beach", "travel", "travel/norway", "travel-2019"]
+    );
+}
@@ -0,0 +1,16 @@
+use photo_album::components::upload::Bbox;
+
+#[test]
+fn finds_overlapping_boxes() {
+    let bbox = |x, y, w, h| Bbox { x, y, w, h };
+
+    let face = bbox(100, 100, 50, 50);
+    assert!(face.overlaps(&bbox(110, 110, 50, 50)));
+    // A padded box drawn around the face
+    assert!(face.overlaps(&bbox(80, 80, 90, 90)));
+    assert!(bbox(80, 80, 90, 90).overlaps(&face));
+    // Neighbours in a group photo touch at the edges
+    assert!(!face.overlaps(&bbox(140, 100, 50, 50)));
+    assert!(!face.overlaps(&bbox(150, 100, 50, 50)));
+    assert!(!face.overlaps(&bbox(0, 0, 20, 20)));
+}